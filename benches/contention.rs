@@ -0,0 +1,47 @@
+//! Measures how many jobs per second a growing number of producers
+//! submit through a single borrowed pool, without waiting for them to run
+//!
+//! Run with `cargo bench --bench contention`
+
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
+
+const THREADS: usize = 4;
+const JOBS_PER_PRODUCER: usize = 50_000;
+const ROUNDS: usize = 5;
+
+/// Returns the time `producers` threads take to submit their jobs
+fn round(pool: &ThreadPool, producers: usize) -> Duration {
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..producers {
+            scope.spawn(|| {
+                for i in 0..JOBS_PER_PRODUCER {
+                    pool.execute(move || {
+                        black_box(i);
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+    pool.join();
+    elapsed
+}
+
+fn main() {
+    let pool = ThreadPool::build(THREADS).unwrap();
+    for producers in [1, 2, 4, 8, 16] {
+        round(&pool, producers);
+        let best = (0..ROUNDS).map(|_| round(&pool, producers)).min().unwrap();
+        let jobs = (producers * JOBS_PER_PRODUCER) as f64;
+        println!(
+            "{:>2} producers: {:>10.0} submissions/s",
+            producers,
+            jobs / best.as_secs_f64()
+        );
+    }
+}
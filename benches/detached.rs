@@ -0,0 +1,50 @@
+//! Compares the per-job overhead of `execute` with the one of
+//! `execute_detached`, which records nothing about the jobs it runs
+//!
+//! The jobs are queued while the pool is paused, so that only the time
+//! the workers take to run them is measured, rather than the wakeups
+//! of the workers draining the queue faster than it is filled
+//!
+//! Run with `cargo bench --bench detached`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
+
+const JOBS: u32 = 200_000;
+const ROUNDS: usize = 5;
+
+/// Returns the mean time the workers take to run a trivial job
+fn round(pool: &ThreadPool, detached: bool) -> Duration {
+    pool.pause();
+    for i in 0..JOBS {
+        let job = move || {
+            black_box(i);
+        };
+        if detached {
+            pool.execute_detached(job);
+        } else {
+            pool.execute(job).unwrap();
+        }
+    }
+    let start = Instant::now();
+    pool.resume();
+    pool.join();
+    start.elapsed() / JOBS
+}
+
+fn main() {
+    for threads in [1, 4] {
+        let pool = ThreadPool::build(threads).unwrap();
+        for (label, detached) in [("execute", false), ("execute_detached", true)] {
+            round(&pool, detached);
+            let best = (0..ROUNDS).map(|_| round(&pool, detached)).min().unwrap();
+            println!(
+                "{} threads, {:>16}: {:>6} ns/job",
+                threads,
+                label,
+                best.as_nanos()
+            );
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Measures how many trivial jobs per second the pool dispatches,
+//! with several producers submitting to pools of growing size
+//!
+//! Run with `cargo bench`
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
+
+const PRODUCERS: usize = 4;
+const JOBS_PER_PRODUCER: usize = 50_000;
+const ROUNDS: usize = 5;
+
+/// Returns the time taken to execute every job of a round
+fn round(pool: &ThreadPool) -> Duration {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|_| {
+            let pool = pool.clone();
+            let counter = Arc::clone(&counter);
+            thread::spawn(move || {
+                for _ in 0..JOBS_PER_PRODUCER {
+                    let counter = Arc::clone(&counter);
+                    pool.execute(move || {
+                        black_box(counter.fetch_add(1, Ordering::Relaxed));
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    pool.join();
+    let elapsed = start.elapsed();
+    assert_eq!(
+        counter.load(Ordering::Relaxed),
+        PRODUCERS * JOBS_PER_PRODUCER
+    );
+    elapsed
+}
+
+fn main() {
+    for threads in [1, 2, 4, 8, 16] {
+        let pool = ThreadPool::build(threads).unwrap();
+        round(&pool);
+        let best = (0..ROUNDS).map(|_| round(&pool)).min().unwrap();
+        let jobs = (PRODUCERS * JOBS_PER_PRODUCER) as f64;
+        println!(
+            "{:>2} threads: {:>10.0} jobs/s",
+            threads,
+            jobs / best.as_secs_f64()
+        );
+    }
+}
@@ -1,191 +1,8023 @@
 use std::{
-    fmt,
-    sync::{mpsc, Arc, Mutex},
-    thread,
-    thread::JoinHandle,
+    any::{Any, TypeId},
+    cell::{Cell, RefCell},
+    cmp::Ordering as CmpOrdering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt, io, iter,
+    marker::PhantomData,
+    mem,
+    num::NonZeroUsize,
+    ops::ControlFlow,
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::Ordering, mpsc, Arc, OnceLock, PoisonError, Weak},
+    time::{Duration, Instant},
 };
 
+#[cfg(loom)]
+use sync::{thread::IsFinished, ClearPoison, WaitWhile};
+use sync::{
+    thread::{self, JoinHandle, ThreadId},
+    AtomicBool, AtomicU64, AtomicUsize, Condvar, Mutex,
+};
+
+/// The primitives the pool synchronizes its threads with: the `std` ones,
+/// or the `loom` ones under `--cfg loom`, so that the model tests explore
+/// the interleavings of the workers and the threads using the pool
+///
+/// The `Arc`s and the channels stay the `std` ones, so a model test
+/// cannot wait on the receivers returned by the pool
+#[cfg(not(loom))]
+mod sync {
+    pub(crate) use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+    pub(crate) use std::sync::{Condvar, Mutex};
+    pub(crate) use std::thread;
+
+    /// Orders the `SeqCst` accesses before it with the ones after it,
+    /// which `std` does already
+    #[inline]
+    pub(crate) fn seq_cst_fence() {}
+}
+
+/// Forwards to `loom::thread_local!`, which does not accept
+/// `const` initializers
+#[cfg(loom)]
+macro_rules! thread_local {
+    () => {};
+    ($(#[$attr:meta])* static $name:ident: $t:ty = const { $init:expr }; $($rest:tt)*) => {
+        loom::thread_local!($(#[$attr])* static $name: $t = $init;);
+        thread_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
+        loom::thread_local!($(#[$attr])* static $name: $t = $init;);
+        thread_local!($($rest)*);
+    };
+}
+
+/// The `loom` counterparts of the `std` primitives, whose model tests run
+/// with `RUSTFLAGS="--cfg loom" cargo test --release --test loom`
+///
+/// `loom` lacks some of the methods the pool calls, which are filled in
+/// here. Its condition variables never time out
+#[cfg(loom)]
+mod sync {
+    pub(crate) use loom::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+    pub(crate) use loom::sync::{Condvar, Mutex, MutexGuard};
+    use std::sync::{LockResult, PoisonError};
+    use std::time::Duration;
+
+    /// Orders the `SeqCst` accesses before it with the ones after it,
+    /// which `loom` only models for fences, as it treats the `SeqCst`
+    /// accesses as `AcqRel` ones
+    pub(crate) fn seq_cst_fence() {
+        loom::sync::atomic::fence(std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub(crate) mod thread {
+        pub(crate) use loom::thread::*;
+        pub(crate) use std::thread::{available_parallelism, panicking, scope, Result};
+
+        /// Stands in for `std::thread::JoinHandle::is_finished`, the
+        /// threads of a model being considered running until joined
+        pub(crate) trait IsFinished {
+            fn is_finished(&self) -> bool;
+        }
+
+        impl<T> IsFinished for JoinHandle<T> {
+            fn is_finished(&self) -> bool {
+                false
+            }
+        }
+    }
+
+    /// Stands in for `std::sync::Mutex::clear_poison`
+    pub(crate) trait ClearPoison {
+        fn clear_poison(&self);
+    }
+
+    impl<T> ClearPoison for Mutex<T> {
+        fn clear_poison(&self) {}
+    }
+
+    /// Whether a wait of `WaitWhile::wait_timeout_while` has timed out,
+    /// which it never has
+    pub(crate) struct WaitTimeoutResult;
+
+    impl WaitTimeoutResult {
+        pub(crate) fn timed_out(&self) -> bool {
+            false
+        }
+    }
+
+    /// Stands in for `std::sync::Condvar::wait_while` and `wait_timeout_while`
+    pub(crate) trait WaitWhile {
+        fn wait_while<'a, T, F>(
+            &self,
+            guard: MutexGuard<'a, T>,
+            condition: F,
+        ) -> LockResult<MutexGuard<'a, T>>
+        where
+            F: FnMut(&mut T) -> bool;
+
+        #[allow(clippy::type_complexity)]
+        fn wait_timeout_while<'a, T, F>(
+            &self,
+            guard: MutexGuard<'a, T>,
+            timeout: Duration,
+            condition: F,
+        ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+        where
+            F: FnMut(&mut T) -> bool;
+    }
+
+    impl WaitWhile for Condvar {
+        fn wait_while<'a, T, F>(
+            &self,
+            mut guard: MutexGuard<'a, T>,
+            mut condition: F,
+        ) -> LockResult<MutexGuard<'a, T>>
+        where
+            F: FnMut(&mut T) -> bool,
+        {
+            while condition(&mut *guard) {
+                guard = self.wait(guard)?;
+            }
+            Ok(guard)
+        }
+
+        fn wait_timeout_while<'a, T, F>(
+            &self,
+            guard: MutexGuard<'a, T>,
+            _timeout: Duration,
+            condition: F,
+        ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+        where
+            F: FnMut(&mut T) -> bool,
+        {
+            match self.wait_while(guard, condition) {
+                Ok(guard) => Ok((guard, WaitTimeoutResult)),
+                Err(err) => Err(PoisonError::new((err.into_inner(), WaitTimeoutResult))),
+            }
+        }
+    }
+}
+
 /// An error struct specifying that a non-positive value has been
 /// passed to a caller
 ///
-/// ```should_panic
-/// use threadpool::ThreadPool;
+/// Superseded by `PoolError::InvalidThreadCount`, into which it can
+/// still be converted. Both implement `std::error::Error`, so the
+/// errors of `build` can be propagated with `?`
+///
+/// ```
+/// use std::error::Error;
+/// use threadpool::ThreadPool;
+/// fn build() -> Result<ThreadPool, Box<dyn Error>> {
+///     Ok(ThreadPool::build(0)?)
+/// }
+/// fn main() {
+///     let err = build().err().unwrap();
+///     assert_eq!(
+///         err.to_string(),
+///         "Invalid thread count: 0. Thread count must be a positive number."
+///     );
+/// }
+/// ```
+#[deprecated(note = "use `PoolError::InvalidThreadCount` instead")]
+#[derive(Debug, Clone)]
+pub struct ThreadCountError {
+    caller: &'static str,
+    invalid_val: usize,
+}
+
+#[allow(deprecated)]
+impl fmt::Display for ThreadCountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: Invalid thread count: {}. Thread count must be a positive number.",
+            self.caller, self.invalid_val
+        )
+    }
+}
+
+#[allow(deprecated)]
+impl std::error::Error for ThreadCountError {}
+
+/// An error enum specifying why an operation on a thread pool failed
+///
+/// # Examples
+///
+/// ```
+/// use threadpool::{PoolError, ThreadPool};
+/// fn main() {
+///     match ThreadPool::build(0) {
+///         Err(PoolError::InvalidThreadCount(count)) => assert_eq!(count, 0),
+///         _ => panic!("A pool with no threads should not be built"),
+///     }
+/// }
+/// ```
+///
+/// Every variant describes itself through `Display`
+///
+/// ```
+/// use std::io;
+/// use threadpool::PoolError;
+/// fn main() {
+///     assert_eq!(
+///         PoolError::InvalidThreadCount(0).to_string(),
+///         "Invalid thread count: 0. Thread count must be a positive number."
+///     );
+///
+///     let err = io::Error::new(io::ErrorKind::WouldBlock, "Resource temporarily unavailable");
+///     assert_eq!(
+///         PoolError::SpawnFailed(err).to_string(),
+///         "Failed to spawn a worker thread: Resource temporarily unavailable"
+///     );
+///
+///     assert_eq!(
+///         PoolError::Shutdown.to_string(),
+///         "The thread pool has been shut down"
+///     );
+///
+///     let err = PoolError::InvalidEnvVar {
+///         name: String::from("THREADS"),
+///         value: String::from("abc"),
+///     };
+///     assert_eq!(
+///         err.to_string(),
+///         "Invalid thread count in THREADS: \"abc\". Thread count must be a number."
+///     );
+/// }
+/// ```
+///
+/// The errors can be boxed into a `Box<dyn Error>`, the spawn failure
+/// exposing its underlying cause as its source
+///
+/// ```
+/// use std::error::Error;
+/// use std::io;
+/// use threadpool::PoolError;
+/// fn main() {
+///     let err: Box<dyn Error> = Box::new(PoolError::SpawnFailed(io::Error::from(
+///         io::ErrorKind::OutOfMemory,
+///     )));
+///     assert!(err.source().is_some());
+///
+///     let err: Box<dyn Error> = Box::new(PoolError::Shutdown);
+///     assert!(err.source().is_none());
+/// }
+/// ```
+#[derive(Debug)]
+pub enum PoolError {
+    /// A non-positive thread count has been passed
+    InvalidThreadCount(usize),
+    /// The OS failed to spawn a worker thread
+    SpawnFailed(io::Error),
+    /// The thread pool has been shut down
+    Shutdown,
+    /// Every worker of the thread pool has died, e.g. from a job
+    /// panicking in `PanicMode::Abort`
+    Dead,
+    /// An environment variable meant to hold a thread count
+    /// holds something else
+    InvalidEnvVar {
+        /// The name of the variable
+        name: String,
+        /// The value of the variable, lossily converted to UTF-8
+        value: String,
+    },
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoolError::InvalidThreadCount(count) => write!(
+                f,
+                "Invalid thread count: {}. Thread count must be a positive number.",
+                count
+            ),
+            PoolError::SpawnFailed(err) => write!(f, "Failed to spawn a worker thread: {}", err),
+            PoolError::Shutdown => write!(f, "The thread pool has been shut down"),
+            PoolError::Dead => write!(f, "Every worker of the thread pool has died"),
+            PoolError::InvalidEnvVar { name, value } => write!(
+                f,
+                "Invalid thread count in {}: {:?}. Thread count must be a number.",
+                name, value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PoolError::SpawnFailed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl From<ThreadCountError> for PoolError {
+    fn from(err: ThreadCountError) -> Self {
+        PoolError::InvalidThreadCount(err.invalid_val)
+    }
+}
+
+/// A struct representing a job that can sent to other threads
+/// and can only be called once
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A queue of jobs replacing the pool's own scheduling, shared by
+/// every worker
+///
+/// The queue decides the order the jobs are run in, so the priorities
+/// are ignored and no job is stolen between workers. Every pushed job
+/// has to be popped eventually, as the workers of a closed pool keep
+/// popping while some jobs are left
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Mutex;
+/// use threadpool::{Job, JobQueue};
+/// struct LifoQueue(Mutex<Vec<Job>>);
+///
+/// impl JobQueue for LifoQueue {
+///     fn push(&self, job: Job) {
+///         self.0.lock().unwrap().push(job);
+///     }
+///
+///     fn pop(&self) -> Option<Job> {
+///         self.0.lock().unwrap().pop()
+///     }
+/// }
+/// fn main() {
+///     let queue = LifoQueue(Mutex::new(Vec::new()));
+///     queue.push(Box::new(|| println!("Pushed")));
+///     assert!(queue.pop().is_some());
+///     assert!(queue.pop().is_none());
+/// }
+/// ```
+pub trait JobQueue {
+    /// Adds a job to the queue
+    fn push(&self, job: Job);
+
+    /// Removes the next job to run from the queue, if any
+    fn pop(&self) -> Option<Job>;
+}
+
+/// How the workers react to a panicking job
+///
+/// Both modes rely on unwinding. In a crate compiled with
+/// `panic = "abort"`, a panicking job aborts the whole process before
+/// the pool sees it: the workers do not catch the panics, the panic
+/// handler is never invoked and `ThreadPool::panic_count` stays at 0.
+/// `ThreadPool::CATCHES_PANICS` tells which strategy the pool got
+///
+/// # Examples
+///
+/// ```
+/// use threadpool::PanicMode;
+/// fn main() {
+///     assert_eq!(PanicMode::default(), PanicMode::Continue);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicMode {
+    /// The panic propagates, poisoning the pool's locks so that every
+    /// worker dies and the next use of the pool panics
+    Abort,
+    /// The panic is caught and the worker moves on to the next job
+    #[default]
+    Continue,
+}
+
+/// What dropping the last handle of a pool does with the queued jobs
+///
+/// # Examples
+///
+/// ```
+/// use threadpool::DropPolicy;
+/// fn main() {
+///     assert_eq!(DropPolicy::default(), DropPolicy::Drain);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// The workers execute every queued job before shutting down
+    #[default]
+    Drain,
+    /// The queued jobs are dropped without being run, the workers
+    /// only finish their running jobs
+    Abandon,
+}
+
+/// A callable invoked with the payload of every panicking job
+type PanicHandler = Arc<dyn Fn(Box<dyn Any + Send>) + Send + Sync>;
+
+/// A callable invoked on every worker's thread with its ID, before
+/// the worker picks up any job
+type WorkerInit = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// A callable invoked on an idle worker's thread with its ID, every time
+/// the worker has waited for a job for the park timeout
+type WorkerMaintenance = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// A custom queue of jobs set through `ThreadPoolBuilder::job_queue`
+type CustomQueue = Arc<dyn JobQueue + Send + Sync>;
+
+/// A callable producing a worker-local value from the worker's ID,
+/// along with the `TypeId` of the value
+type LocalFactory = (TypeId, Arc<dyn Fn(usize) -> Box<dyn Any> + Send + Sync>);
+
+/// A job waiting in the queue along with its scheduling information
+struct PriorityJob {
+    priority: u8,
+    /// The deadline set by `ThreadPool::execute_by_deadline`, the jobs
+    /// without one running after the others of equal priority
+    deadline: Option<Instant>,
+    /// The insertion sequence number, keeping jobs of equal priority
+    /// in submission order
+    seq: u64,
+    job: Job,
+    /// Whether the job was sent through `ThreadPool::execute_detached`,
+    /// so that the worker does not record its execution
+    detached: bool,
+}
+
+impl PartialEq for PriorityJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for PriorityJob {}
+
+impl PartialOrd for PriorityJob {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PriorityJob {
+    /// Orders by priority first, then by deadline, then by submission.
+    /// The earlier deadline or submission is considered greater, as
+    /// `BinaryHeap` is a max-heap
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        let deadline = match (self.deadline, other.deadline) {
+            (Some(deadline), Some(other)) => other.cmp(&deadline),
+            (Some(_), None) => CmpOrdering::Greater,
+            (None, Some(_)) => CmpOrdering::Less,
+            (None, None) => CmpOrdering::Equal,
+        };
+        self.priority
+            .cmp(&other.priority)
+            .then(deadline)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// A queue of jobs owned by a worker, which the other workers steal
+/// from once their own queue is empty
+struct LocalQueue {
+    jobs: Mutex<BinaryHeap<PriorityJob>>,
+    /// The length of `jobs`, readable without taking the lock
+    len: AtomicUsize,
+}
+
+/// A job waiting for its deadline in the scheduler
+struct DelayedJob {
+    deadline: Instant,
+    /// The insertion sequence number, keeping jobs of equal deadline
+    /// in submission order
+    seq: u64,
+    job: Job,
+}
+
+impl PartialEq for DelayedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for DelayedJob {}
+
+impl PartialOrd for DelayedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DelayedJob {
+    /// The job with the earliest deadline is considered greater,
+    /// as `BinaryHeap` is a max-heap
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other
+            .deadline
+            .cmp(&self.deadline)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// The jobs waiting for their deadline
+struct Timers {
+    jobs: BinaryHeap<DelayedJob>,
+    next_seq: u64,
+    /// Whether the scheduler should drop the pending jobs and shut down
+    closed: bool,
+}
+
+/// The state shared between a thread pool and its scheduler thread
+struct Scheduler {
+    timers: Mutex<Timers>,
+    /// Notified when a job is scheduled or the scheduler is closed
+    timer_changed: Condvar,
+}
+
+impl Scheduler {
+    /// Submits the jobs to `shared` as their deadlines pass, until
+    /// the scheduler is closed
+    fn run(&self, shared: &Shared) {
+        let mut timers = self.timers.lock().unwrap();
+        loop {
+            if timers.closed {
+                return;
+            }
+            match timers.jobs.peek() {
+                None => timers = self.timer_changed.wait(timers).unwrap(),
+                Some(next) => {
+                    let now = Instant::now();
+                    if next.deadline <= now {
+                        let job = timers.jobs.pop().unwrap().job;
+                        drop(timers);
+                        shared.push_job(ThreadPool::DEFAULT_PRIORITY, job);
+                        timers = self.timers.lock().unwrap();
+                    } else {
+                        let timeout = next.deadline - now;
+                        timers = self.timer_changed.wait_timeout(timers, timeout).unwrap().0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How often the autoscaling monitor samples the pool
+const AUTOSCALE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The number of samples with queued jobs after which the pool grows
+const AUTOSCALE_BACKLOG_TICKS: u32 = 2;
+
+/// The number of samples with idle workers after which the pool shrinks
+const AUTOSCALE_IDLE_TICKS: u32 = 20;
+
+/// How often the supervisor looks for dead workers
+const SUPERVISE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The minimum time between two respawns of dead workers
+const RESPAWN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The time between two respawns the backoff doubles up to
+const RESPAWN_BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+/// The state shared between a thread pool and its autoscaling monitor
+/// and supervisor threads
+struct Monitor {
+    /// Whether the monitor and supervisor should shut down
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+impl Monitor {
+    /// Resizes the pool between `min` and `max` threads until
+    /// it is dropped or closed
+    ///
+    /// The pool is only referenced weakly, so that the monitor does not
+    /// keep it alive, and grows once jobs have been queued for a few
+    /// samples in a row, or shrinks once workers have been idle for a while
+    fn run(&self, pool: Weak<Inner>, min: usize, max: usize) {
+        let mut backlog_ticks = 0;
+        let mut idle_ticks = 0;
+        let mut stopped = self.stopped.lock().unwrap();
+        loop {
+            stopped = self
+                .stop
+                .wait_timeout_while(stopped, AUTOSCALE_INTERVAL, |stopped| !*stopped)
+                .unwrap()
+                .0;
+            if *stopped {
+                return;
+            }
+            drop(stopped);
+
+            // The pool is dropped on this thread if the last handle goes
+            // meanwhile, which stops the monitor
+            if let Some(inner) = pool.upgrade() {
+                let pool = ThreadPool { inner };
+                let thread_count = pool.thread_count();
+                let queued = pool.queued_count();
+                let active = pool.active_count();
+
+                // The jobs queued while paused do not call for more workers
+                backlog_ticks = if queued > 0 && !pool.is_paused() {
+                    backlog_ticks + 1
+                } else {
+                    0
+                };
+                idle_ticks = if queued == 0 && active < thread_count {
+                    idle_ticks + 1
+                } else {
+                    0
+                };
+                if backlog_ticks >= AUTOSCALE_BACKLOG_TICKS && thread_count < max {
+                    let _ = pool.set_num_threads((thread_count + queued).min(max));
+                    backlog_ticks = 0;
+                } else if idle_ticks >= AUTOSCALE_IDLE_TICKS && thread_count > min {
+                    let _ = pool.set_num_threads(active.max(min));
+                    idle_ticks = 0;
+                }
+            } else {
+                return;
+            }
+
+            stopped = self.stopped.lock().unwrap();
+        }
+    }
+
+    /// Replaces the dead workers of the pool until it is dropped or closed
+    ///
+    /// The pool is only referenced weakly, like in `run`. The workers
+    /// dying again shortly after a respawn double the time until
+    /// the next one, so that a crash loop does not keep spawning threads
+    fn supervise(&self, pool: Weak<Inner>) {
+        let mut backoff = RESPAWN_BACKOFF;
+        let mut last_respawn: Option<Instant> = None;
+        let mut stopped = self.stopped.lock().unwrap();
+        loop {
+            stopped = self
+                .stop
+                .wait_timeout_while(stopped, SUPERVISE_INTERVAL, |stopped| !*stopped)
+                .unwrap()
+                .0;
+            if *stopped {
+                return;
+            }
+            drop(stopped);
+
+            let Some(inner) = pool.upgrade() else {
+                return;
+            };
+            let due = last_respawn.is_none_or(|last| last.elapsed() >= backoff);
+            if due && inner.respawn_dead_workers() > 0 {
+                backoff = match last_respawn {
+                    Some(last) if last.elapsed() < backoff * 2 => {
+                        (backoff * 2).min(RESPAWN_BACKOFF_MAX)
+                    }
+                    _ => RESPAWN_BACKOFF,
+                };
+                last_respawn = Some(Instant::now());
+            }
+            // The pool is dropped on this thread if the last handle
+            // went meanwhile, which stops the supervisor
+            drop(inner);
+
+            stopped = self.stopped.lock().unwrap();
+        }
+    }
+}
+
+/// The state shared between a thread pool and its workers
+struct Shared {
+    /// The local queues, one per worker the pool has been built with.
+    /// The workers spawned later share them
+    queues: Vec<LocalQueue>,
+    /// The queue replacing the local ones, if the builder was given one
+    job_queue: Option<CustomQueue>,
+    /// The queue `execute` starts looking for the least loaded queue from
+    next_queue: AtomicUsize,
+    /// The jobs sent through `execute_on`, keyed by the ID of the worker
+    /// they have to run on, each in submission order
+    pinned: Mutex<HashMap<usize, VecDeque<Job>>>,
+    /// The number of jobs in `pinned`, so that the workers only lock it
+    /// when there are some
+    pinned_count: AtomicUsize,
+    /// The next insertion sequence number
+    next_seq: AtomicU64,
+    /// Taken to sleep on `job_available` and `not_full`, and to notify
+    /// them without losing a wakeup
+    control: Mutex<()>,
+    /// Notified when a job is pushed while a worker sleeps, a worker is
+    /// asked to shut down or the pool is closed
+    job_available: Condvar,
+    /// Notified when a job is popped from a bounded queue or the pool
+    /// is closed
+    not_full: Condvar,
+    /// The number of workers sleeping on `job_available`
+    sleepers: AtomicUsize,
+    /// The number of workers asked to shut down that have not
+    /// picked up the request yet
+    terminate_requests: AtomicUsize,
+    /// Whether the pool has stopped accepting jobs
+    closed: AtomicBool,
+    /// Whether the workers have been asked to stop picking up jobs,
+    /// until they are woken up on `job_available` by `resume`
+    paused: AtomicBool,
+    /// Cancelled once the pool is closed, passed to the jobs sent through
+    /// `execute_with_shutdown`
+    shutdown_token: CancellationToken,
+    /// The maximum number of queued jobs, if the queue is bounded
+    capacity: Option<usize>,
+    panic_count: AtomicUsize,
+    /// The messages of the latest panicking jobs, the oldest first
+    panic_log: Mutex<VecDeque<String>>,
+    panic_handler: Option<PanicHandler>,
+    panic_mode: PanicMode,
+    worker_init: Option<WorkerInit>,
+    worker_locals: Vec<LocalFactory>,
+    /// How long an idle worker waits for a job before running
+    /// the maintenance, if it ever stops waiting
+    park_timeout: Option<Duration>,
+    maintenance: Option<WorkerMaintenance>,
+    /// The IDs of the CPU cores the workers are pinned to, in turn
+    core_ids: Vec<usize>,
+    /// Whether the workers log their starting up and shutting down
+    lifecycle_logging: bool,
+    /// Whether the panic hook stays silent on the workers' threads
+    panic_hook_suppression: bool,
+    /// The name of the pool, used in the log messages
+    name: Option<String>,
+    /// The pool spawning its workers as jobs arrive, set once built
+    /// if it spawns them lazily
+    on_demand: OnceLock<Weak<Inner>>,
+    /// The number of jobs waiting to be picked up by a worker
+    queued_count: AtomicUsize,
+    /// The number of jobs being executed by the workers
+    active_count: AtomicUsize,
+    /// The labels of the jobs of `ThreadPool::execute_labeled` being
+    /// executed along with the ID of their worker, keyed by the worker's
+    /// thread, as the workers of the pools sharing a custom queue
+    /// may have the same ID
+    labels: Mutex<HashMap<ThreadId, (usize, &'static str)>>,
+    /// The number of workers whose job waits on the results of other
+    /// jobs of the pool, only tracked in debug builds
+    blocked_workers: AtomicUsize,
+    /// The number of jobs the workers have finished, panicking or not
+    completed_count: AtomicUsize,
+    /// The nanoseconds the workers have spent running jobs
+    busy_nanos: AtomicU64,
+    /// When the pool was built, from which `utilization` is measured
+    created: Instant,
+    /// The number of jobs that have been submitted but not finished yet
+    outstanding: AtomicUsize,
+    /// The number of jobs whose panic poisoned the pool, which never
+    /// finish and are settled once the poison is cleared
+    aborted: AtomicUsize,
+    /// Taken to wait on `all_done` and to notify it without losing a wakeup
+    all_done_lock: Mutex<()>,
+    all_done: Condvar,
+    /// The number of workers spawned whose threads have not exited yet
+    live_workers: AtomicUsize,
+    /// Whether the last live worker has died from a panic, leaving
+    /// none to run the queued jobs
+    dead: AtomicBool,
+    /// The IDs of the workers whose threads have exited,
+    /// but have not been joined yet
+    exited: Mutex<Vec<usize>>,
+    worker_exited: Condvar,
+}
+
+/// A guard waking up every thread blocked on the pool when dropped,
+/// so that they observe the locks it has been dropped after
+struct WakeAll<'a> {
+    shared: &'a Shared,
+}
+
+impl Drop for WakeAll<'_> {
+    fn drop(&mut self) {
+        self.shared.job_available.notify_all();
+        self.shared.not_full.notify_all();
+        self.shared.all_done.notify_all();
+    }
+}
+
+impl Shared {
+    /// Returns a `Shared` with `queues` empty local queues, sharing
+    /// the preallocated room for jobs between them
+    fn new(builder: &ThreadPoolBuilder, queues: usize) -> Shared {
+        // A custom queue replaces the local ones, so they stay unallocated
+        let prealloc = match builder.job_queue {
+            Some(_) => 0,
+            None => builder.prealloc_jobs.div_ceil(queues.max(1)),
+        };
+        Shared {
+            queues: (0..queues)
+                .map(|_| LocalQueue {
+                    jobs: Mutex::new(BinaryHeap::with_capacity(prealloc)),
+                    len: AtomicUsize::new(0),
+                })
+                .collect(),
+            job_queue: builder.job_queue.clone(),
+            next_queue: AtomicUsize::new(0),
+            pinned: Mutex::new(HashMap::new()),
+            pinned_count: AtomicUsize::new(0),
+            next_seq: AtomicU64::new(0),
+            control: Mutex::new(()),
+            job_available: Condvar::new(),
+            not_full: Condvar::new(),
+            sleepers: AtomicUsize::new(0),
+            terminate_requests: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            shutdown_token: CancellationToken {
+                cancelled: Arc::new(AtomicBool::new(false)),
+            },
+            capacity: builder.queue_capacity,
+            panic_count: AtomicUsize::new(0),
+            panic_log: Mutex::new(VecDeque::new()),
+            panic_handler: builder.panic_handler.clone(),
+            panic_mode: builder.panic_mode,
+            worker_init: builder.worker_init.clone(),
+            worker_locals: builder.worker_locals.clone(),
+            park_timeout: builder.park_timeout,
+            maintenance: builder.maintenance.clone(),
+            core_ids: builder.core_ids.clone(),
+            lifecycle_logging: builder.lifecycle_logging,
+            panic_hook_suppression: builder.panic_hook_suppression,
+            name: builder.name.clone(),
+            on_demand: OnceLock::new(),
+            queued_count: AtomicUsize::new(0),
+            active_count: AtomicUsize::new(0),
+            labels: Mutex::new(HashMap::new()),
+            blocked_workers: AtomicUsize::new(0),
+            completed_count: AtomicUsize::new(0),
+            busy_nanos: AtomicU64::new(0),
+            created: Instant::now(),
+            outstanding: AtomicUsize::new(0),
+            aborted: AtomicUsize::new(0),
+            all_done_lock: Mutex::new(()),
+            all_done: Condvar::new(),
+            live_workers: AtomicUsize::new(0),
+            dead: AtomicBool::new(false),
+            exited: Mutex::new(Vec::new()),
+            worker_exited: Condvar::new(),
+        }
+    }
+
+    /// Returns how the log messages refer to the worker with ID `id`
+    fn describe_worker(&self, id: usize) -> String {
+        match &self.name {
+            Some(name) => format!("Thread {} of pool {}", id, name),
+            None => format!("Thread {}", id),
+        }
+    }
+
+    /// Returns whether the current thread is one of the pool's workers
+    fn is_current(&self) -> bool {
+        std::ptr::eq(CURRENT_POOL.with(|pool| pool.get()), self)
+    }
+
+    /// Returns whether the pool has stopped accepting jobs
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Returns the local queue holding the fewest jobs
+    fn least_loaded_queue(&self) -> &LocalQueue {
+        // Starting from a rotating queue spreads the jobs between
+        // the queues of equal length
+        let start = self.next_queue.fetch_add(1, Ordering::Relaxed);
+        let count = self.queues.len();
+        (0..count)
+            .map(|offset| &self.queues[(start + offset) % count])
+            .min_by_key(|queue| queue.len.load(Ordering::Relaxed))
+            .unwrap()
+    }
+
+    /// Pushes jobs to the locked local `queue`, returns `false` without
+    /// running them if the pool is closed. The `detached` jobs are not
+    /// recorded by the worker running them
+    ///
+    /// The pool is closed before its workers check that the queues are
+    /// empty under their locks, so checking it under the lock ensures
+    /// that the pushed jobs are executed
+    fn enqueue(
+        &self,
+        queue: &LocalQueue,
+        jobs: &mut BinaryHeap<PriorityJob>,
+        priority: u8,
+        deadline: Option<Instant>,
+        new_jobs: Vec<Job>,
+        detached: bool,
+    ) -> bool {
+        if self.is_closed() {
+            return false;
+        }
+        let count = new_jobs.len();
+        self.outstanding.fetch_add(count, Ordering::SeqCst);
+        for job in new_jobs {
+            let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+            jobs.push(PriorityJob {
+                priority,
+                deadline,
+                seq,
+                job,
+                detached,
+            });
+        }
+        queue.len.store(jobs.len(), Ordering::Relaxed);
+        self.queued_count.fetch_add(count, Ordering::SeqCst);
+        true
+    }
+
+    /// Wakes up `count` sleeping workers, if any
+    fn wake_workers(&self, count: usize) {
+        // Pairs with the sleeping workers incrementing `sleepers` before
+        // checking `queued_count`, so that either the worker sees the job
+        // or the pushing thread sees the worker
+        sync::seq_cst_fence();
+        if count > 0 && self.sleepers.load(Ordering::SeqCst) > 0 {
+            let _control = self.control.lock().unwrap();
+            if count == 1 {
+                self.job_available.notify_one();
+            } else {
+                self.job_available.notify_all();
+            }
+        }
+    }
+
+    /// Pushes a job to the least loaded local queue, blocking while
+    /// a bounded queue is full, returns `false` without running it
+    /// if the pool is closed or dead
+    ///
+    /// A worker pushing a job does not wait for a free slot, since every
+    /// worker could be waiting for the others
+    fn push_job(&self, priority: u8, job: Job) -> bool {
+        self.push_job_by(priority, None, job)
+    }
+
+    /// Pushes a job like `push_job`, returns why it was dropped instead
+    fn try_push_job(&self, priority: u8, job: Job) -> Result<(), PoolError> {
+        if self.push_job(priority, job) {
+            Ok(())
+        } else if self.is_dead() {
+            Err(PoolError::Dead)
+        } else {
+            Err(PoolError::Shutdown)
+        }
+    }
+
+    /// Pushes a job like `push_job`, ordered by `deadline` among the jobs
+    /// of equal priority
+    ///
+    /// A custom queue orders its jobs itself, so the deadline is ignored
+    fn push_job_by(&self, priority: u8, deadline: Option<Instant>, job: Job) -> bool {
+        if self.rejects_jobs() {
+            return false;
+        }
+        if let Some(queue) = &self.job_queue {
+            let control = self.control.lock().unwrap();
+            let is_current = self.is_current();
+            let _control = self
+                .not_full
+                .wait_while(control, |_| {
+                    !self.is_closed() && !is_current && self.is_full()
+                })
+                .unwrap();
+            if self.is_closed() {
+                return false;
+            }
+            self.enqueue_custom(queue.as_ref(), job);
+            drop(_control);
+            self.spawn_on_demand();
+            return true;
+        }
+        if self.capacity.is_some() {
+            let control = self.control.lock().unwrap();
+            let is_current = self.is_current();
+            let _control = self
+                .not_full
+                .wait_while(control, |_| {
+                    !self.is_closed() && !is_current && self.is_full()
+                })
+                .unwrap();
+            let queue = self.least_loaded_queue();
+            if !self.enqueue(
+                queue,
+                &mut queue.jobs.lock().unwrap(),
+                priority,
+                deadline,
+                vec![job],
+                false,
+            ) {
+                return false;
+            }
+            // The control lock is held, so the sleeping workers can
+            // be notified directly
+            self.job_available.notify_one();
+            drop(_control);
+            self.spawn_on_demand();
+            return true;
+        }
+
+        let queue = self.least_loaded_queue();
+        if !self.enqueue(
+            queue,
+            &mut queue.jobs.lock().unwrap(),
+            priority,
+            deadline,
+            vec![job],
+            false,
+        ) {
+            return false;
+        }
+        self.wake_workers(1);
+        self.spawn_on_demand();
+        true
+    }
+
+    /// Pushes several jobs, spread over the local queues so that each
+    /// is locked once, blocking while a bounded queue is full, returns
+    /// `false` without running the remaining jobs if the pool is closed
+    /// or dead
+    fn push_jobs(&self, priority: u8, jobs: Vec<Job>) -> bool {
+        if self.rejects_jobs() {
+            return false;
+        }
+        if self.capacity.is_some() || self.job_queue.is_some() {
+            return jobs.into_iter().all(|job| self.push_job(priority, job));
+        }
+
+        let count = jobs.len();
+        let mut batches: Vec<Vec<Job>> = (0..self.queues.len()).map(|_| Vec::new()).collect();
+        for (i, job) in jobs.into_iter().enumerate() {
+            batches[i % self.queues.len()].push(job);
+        }
+        for (queue, batch) in self.queues.iter().zip(batches) {
+            if !batch.is_empty()
+                && !self.enqueue(
+                    queue,
+                    &mut queue.jobs.lock().unwrap(),
+                    priority,
+                    None,
+                    batch,
+                    false,
+                )
+            {
+                return false;
+            }
+        }
+        self.wake_workers(count);
+        for _ in 0..count {
+            self.spawn_on_demand();
+        }
+        true
+    }
+
+    /// Pushes a job the worker runs without recording it, returns `false`
+    /// without running it if the pool is closed or dead
+    ///
+    /// The bounded and custom queues track every job they hold,
+    /// so the job is pushed like any other to them
+    fn push_detached(&self, job: Job) -> bool {
+        if self.rejects_jobs() {
+            return false;
+        }
+        if self.capacity.is_some() || self.job_queue.is_some() {
+            return self.push_job(ThreadPool::DEFAULT_PRIORITY, job);
+        }
+        let queue = self.least_loaded_queue();
+        let mut jobs = queue.jobs.lock().unwrap();
+        if !self.enqueue(
+            queue,
+            &mut jobs,
+            ThreadPool::DEFAULT_PRIORITY,
+            None,
+            vec![job],
+            true,
+        ) {
+            return false;
+        }
+        drop(jobs);
+        self.wake_workers(1);
+        self.spawn_on_demand();
+        true
+    }
+
+    /// Spawns a worker for a pushed job, if the pool spawns them lazily
+    /// and more jobs are queued than workers are sleeping
+    ///
+    /// It is called without any lock of the pool held, after the job has
+    /// been pushed, so that the spawned worker finds it. A worker spawning
+    /// another does not wait for the workers' lock, as it is held while the
+    /// pool joins its workers
+    fn spawn_on_demand(&self) {
+        let Some(inner) = self.on_demand.get().and_then(Weak::upgrade) else {
+            return;
+        };
+        if self.queued_count.load(Ordering::SeqCst) <= self.sleepers.load(Ordering::SeqCst) {
+            return;
+        }
+        let workers = if self.is_current() {
+            inner.workers.try_lock().ok()
+        } else {
+            inner.workers.lock().ok()
+        };
+        if let Some(mut workers) = workers {
+            inner.spawn_lazily(&mut workers);
+        }
+    }
+
+    /// Pushes a job to the custom `queue`, with the control lock held
+    /// and the pool checked open
+    ///
+    /// The job is counted as queued once pushed, so that a worker
+    /// reserving a job by decrementing `queued_count` always finds one
+    fn enqueue_custom(&self, queue: &(dyn JobQueue + Send + Sync), job: Job) {
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+        queue.push(job);
+        self.queued_count.fetch_add(1, Ordering::SeqCst);
+        self.job_available.notify_one();
+    }
+
+    /// Pops a job from the custom `queue`, if one is counted as queued
+    fn pop_custom(&self, queue: &(dyn JobQueue + Send + Sync)) -> Option<Job> {
+        self.queued_count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_sub(1)
+            })
+            .ok()?;
+        let Some(job) = queue.pop() else {
+            // The queue has lost track of the job, the reservation
+            // is given back
+            self.queued_count.fetch_add(1, Ordering::SeqCst);
+            return None;
+        };
+        if self.capacity.is_some() {
+            let _control = self.control.lock().unwrap();
+            self.not_full.notify_one();
+        }
+        Some(job)
+    }
+
+    /// Pushes a job only worker `id` may run, returns `false` without
+    /// running it if the pool is closed
+    ///
+    /// The job is pushed under the control lock, so that worker `id`
+    /// checks for it before sleeping without losing a wakeup
+    fn push_pinned(&self, id: usize, job: Job) -> bool {
+        let _control = self.control.lock().unwrap();
+        if self.is_closed() {
+            return false;
+        }
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+        self.pinned
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_default()
+            .push_back(job);
+        self.pinned_count.fetch_add(1, Ordering::SeqCst);
+        // The sleeping worker `id` cannot be woken up on its own
+        self.job_available.notify_all();
+        true
+    }
+
+    /// Pops the oldest job pinned to worker `id`
+    fn pop_pinned(&self, id: usize) -> Option<Job> {
+        if self.pinned_count.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let mut pinned = self.pinned.lock().unwrap();
+        let jobs = pinned.get_mut(&id)?;
+        let job = jobs.pop_front()?;
+        if jobs.is_empty() {
+            pinned.remove(&id);
+        }
+        self.pinned_count.fetch_sub(1, Ordering::SeqCst);
+        Some(job)
+    }
+
+    /// Returns whether some jobs are pinned to worker `id`
+    fn has_pinned(&self, id: usize) -> bool {
+        self.pinned_count.load(Ordering::SeqCst) > 0
+            && self.pinned.lock().unwrap().contains_key(&id)
+    }
+
+    /// Returns whether every worker has died while the pool is open,
+    /// so that a pushed job would never run
+    fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::SeqCst) && !self.is_closed()
+    }
+
+    /// Returns whether every worker has died, logging that the pushed
+    /// jobs are dropped rather than queued for no worker to run them
+    fn rejects_jobs(&self) -> bool {
+        let dead = self.is_dead();
+        if dead {
+            log::warn!("A job was dropped, as every worker of the thread pool has died");
+        }
+        dead
+    }
+
+    /// Returns whether a new job has to wait for a free slot
+    fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|capacity| self.queued_count.load(Ordering::SeqCst) >= capacity)
+    }
+
+    /// Pops the job of highest priority from `queue`, along with
+    /// whether it is detached
+    fn pop_from(&self, queue: &LocalQueue) -> Option<(Job, bool)> {
+        if queue.len.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let mut jobs = queue.jobs.lock().unwrap();
+        let job = jobs.pop()?;
+        queue.len.store(jobs.len(), Ordering::Relaxed);
+        self.queued_count.fetch_sub(1, Ordering::SeqCst);
+        drop(jobs);
+
+        if self.capacity.is_some() {
+            let _control = self.control.lock().unwrap();
+            self.not_full.notify_one();
+        }
+        Some((job.job, job.detached))
+    }
+
+    /// Pops a job from the local queue of worker `id`, or steals one
+    /// from the other queues if it is empty, along with whether it
+    /// is detached
+    fn find_job(&self, id: usize) -> Option<(Job, bool)> {
+        if let Some(queue) = &self.job_queue {
+            return self.pop_custom(queue.as_ref()).map(|job| (job, false));
+        }
+        let count = self.queues.len();
+        (0..count).find_map(|offset| self.pop_from(&self.queues[(id + offset) % count]))
+    }
+
+    /// Returns whether every local queue is empty, checked under their locks
+    fn is_drained(&self) -> bool {
+        if self.job_queue.is_some() {
+            // The jobs are counted under the control lock the pool
+            // is closed under
+            return self.queued_count.load(Ordering::SeqCst) == 0;
+        }
+        self.queues
+            .iter()
+            .all(|queue| queue.jobs.lock().unwrap().is_empty())
+    }
+
+    /// Takes a request to shut down, returns whether there was one
+    fn take_terminate_request(&self) -> bool {
+        self.terminate_requests
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |requests| {
+                requests.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Blocks until there is a job for worker `id`, returns `None`
+    /// if the worker should shut down instead
+    ///
+    /// Requests to shut down take precedence over the queued jobs, while
+    /// a closed pool is drained before the workers shut down. The jobs
+    /// pinned to the worker cannot be run by another one, so they
+    /// take precedence over both. A paused pool only hands out jobs
+    /// once it is closed, so that shutting it down does not block
+    ///
+    /// The job is returned along with whether it is detached
+    fn next_job(&self, id: usize) -> Option<(Job, bool)> {
+        loop {
+            let paused = self.paused.load(Ordering::SeqCst) && !self.is_closed();
+            if let Some(job) = self.pop_pinned(id).filter(|_| !paused) {
+                return Some((job, false));
+            }
+            if self.take_terminate_request() {
+                return None;
+            }
+            if !paused {
+                if let Some(job) = self.find_job(id) {
+                    return Some(job);
+                }
+                // Checked once the pool is seen closed, as no job can be
+                // pinned afterwards
+                if self.is_closed() && self.is_drained() && !self.has_pinned(id) {
+                    return None;
+                }
+            }
+
+            let control = self.control.lock().unwrap();
+            self.sleepers.fetch_add(1, Ordering::SeqCst);
+            sync::seq_cst_fence();
+            let has_work = self.terminate_requests.load(Ordering::SeqCst) > 0
+                || self.is_closed()
+                || !self.paused.load(Ordering::SeqCst)
+                    && (self.queued_count.load(Ordering::SeqCst) > 0 || self.has_pinned(id));
+            let mut timed_out = false;
+            if !has_work {
+                match self.park_timeout {
+                    None => drop(self.job_available.wait(control).unwrap()),
+                    Some(timeout) => {
+                        let (_control, result) =
+                            self.job_available.wait_timeout(control, timeout).unwrap();
+                        timed_out = result.timed_out();
+                    }
+                }
+            }
+            self.sleepers.fetch_sub(1, Ordering::SeqCst);
+
+            // Run without the control lock, as it is user code
+            if let Some(maintenance) = self.maintenance.as_ref().filter(|_| timed_out) {
+                // A panicking maintenance must not take the worker down with it
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| maintenance(id)));
+            }
+        }
+    }
+
+    /// Closes the pool and wakes up every blocked worker and producer
+    ///
+    /// Returns the number of jobs queued when the pool is closed,
+    /// or 0 if it already was
+    fn close(&self) -> usize {
+        // The lock may have been poisoned by a job in `PanicMode::Abort`
+        let _control = self.control.lock().unwrap_or_else(PoisonError::into_inner);
+        let was_closed = self.closed.swap(true, Ordering::SeqCst);
+        self.shutdown_token.cancelled.store(true, Ordering::Release);
+        self.job_available.notify_all();
+        self.not_full.notify_all();
+        if was_closed {
+            return 0;
+        }
+        // Taking the queue locks waits for the jobs being pushed by
+        // the producers that saw the pool open
+        for queue in &self.queues {
+            drop(queue.jobs.lock().unwrap_or_else(PoisonError::into_inner));
+        }
+        self.queued_count.load(Ordering::SeqCst) + self.pinned_count.load(Ordering::SeqCst)
+    }
+
+    /// Asks `count` workers to shut down
+    fn terminate_workers(&self, count: usize) {
+        let _control = self.control.lock().unwrap();
+        self.terminate_requests.fetch_add(count, Ordering::SeqCst);
+        self.job_available.notify_all();
+    }
+
+    /// Removes every queued job, returns them
+    fn drain(&self) -> Vec<Job> {
+        let mut drained = Vec::new();
+        for queue in &self.queues {
+            let mut jobs = queue.jobs.lock().unwrap();
+            queue.len.store(0, Ordering::Relaxed);
+            self.queued_count.fetch_sub(jobs.len(), Ordering::SeqCst);
+            drained.extend(jobs.drain().map(|job| job.job));
+        }
+        if let Some(queue) = &self.job_queue {
+            drained.extend(iter::from_fn(|| self.pop_custom(queue.as_ref())));
+        }
+        let mut pinned = self.pinned.lock().unwrap();
+        self.pinned_count.store(0, Ordering::SeqCst);
+        drained.extend(pinned.drain().flat_map(|(_, jobs)| jobs));
+        drop(pinned);
+        if self.capacity.is_some() {
+            let _control = self.control.lock().unwrap();
+            self.not_full.notify_all();
+        }
+        drained
+    }
+
+    /// Drops every queued job without running it, returns their number
+    fn clear(&self) -> usize {
+        let discarded = self.drain();
+        let count = discarded.len();
+
+        // The jobs are dropped outside the locks, as dropping them
+        // can run user code
+        drop(discarded);
+        if count > 0 {
+            self.finish_jobs(count);
+        }
+        count
+    }
+
+    /// Records the message of a panicking job, dropping the oldest one
+    /// once the log is full
+    fn log_panic(&self, payload: &(dyn Any + Send)) {
+        let message = panic_message(payload).unwrap_or("Box<dyn Any>");
+        let mut panic_log = self.panic_log.lock().unwrap();
+        if panic_log.len() == ThreadPool::PANIC_LOG_CAPACITY {
+            panic_log.pop_front();
+        }
+        panic_log.push_back(message.to_string());
+    }
+
+    /// Resumes the panic of a job while holding the locks of the pool,
+    /// poisoning them for the other workers and for the threads waiting
+    /// in `join`
+    fn poison(&self, payload: Box<dyn Any + Send>) -> ! {
+        self.aborted.fetch_add(1, Ordering::SeqCst);
+        // Declared first so that it is dropped last, once the locks
+        // have been poisoned
+        let _wake_all = WakeAll { shared: self };
+        let _control = self.control.lock().unwrap();
+        let _queues: Vec<_> = self
+            .queues
+            .iter()
+            .map(|queue| queue.jobs.lock().unwrap())
+            .collect();
+        let _all_done = self.all_done_lock.lock().unwrap();
+        panic::resume_unwind(payload)
+    }
+
+    /// Clears the poison left on the locks by `poison`, so that they can
+    /// be locked again, and settles the jobs that poisoned them, so that
+    /// `join` does not wait for them forever
+    fn clear_poison(&self) {
+        self.control.clear_poison();
+        for queue in &self.queues {
+            queue.jobs.clear_poison();
+        }
+        self.pinned.clear_poison();
+        self.panic_log.clear_poison();
+        self.all_done_lock.clear_poison();
+        self.exited.clear_poison();
+
+        let aborted = self.aborted.swap(0, Ordering::SeqCst);
+        if aborted > 0 {
+            self.active_count.fetch_sub(aborted, Ordering::Relaxed);
+            self.finish_jobs(aborted);
+        }
+    }
+
+    /// Records that `count` jobs have finished or been discarded, waking up
+    /// the threads waiting in `join` if they were the last outstanding ones
+    fn finish_jobs(&self, count: usize) {
+        if self.outstanding.fetch_sub(count, Ordering::SeqCst) == count {
+            let _all_done = self.all_done_lock.lock().unwrap();
+            self.all_done.notify_all();
+        }
+    }
+
+    /// Blocks until no job is outstanding or `timeout` has elapsed,
+    /// returns whether every job has finished
+    fn wait_all_done(&self, timeout: Option<Duration>) -> bool {
+        let all_done = self.all_done_lock.lock().unwrap();
+        let outstanding = |_: &mut ()| self.outstanding.load(Ordering::SeqCst) > 0;
+        match timeout {
+            None => {
+                let _all_done = self.all_done.wait_while(all_done, outstanding).unwrap();
+                true
+            }
+            Some(timeout) => {
+                let (_all_done, result) = self
+                    .all_done
+                    .wait_timeout_while(all_done, timeout, outstanding)
+                    .unwrap();
+                !result.timed_out()
+            }
+        }
+    }
+}
+
+/// Pins the current thread to the CPU core `core_id`, returns whether
+/// it succeeded
+#[cfg(feature = "core_affinity")]
+fn pin_to_core(core_id: usize) -> bool {
+    core_affinity::set_for_current(core_affinity::CoreId { id: core_id })
+}
+
+/// Pinning threads requires the `core_affinity` feature, so it always fails
+#[cfg(not(feature = "core_affinity"))]
+fn pin_to_core(_core_id: usize) -> bool {
+    false
+}
+
+/// Wraps `job` so that it runs in the `tracing` span active when
+/// it is submitted, which the events it emits are then recorded under
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use threadpool::ThreadPool;
+/// use tracing::{span, Event, Subscriber};
+/// use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+/// use tracing_subscriber::registry::{LookupSpan, Registry};
+/// struct SpanRecorder(Arc<Mutex<Vec<String>>>);
+///
+/// impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+///     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+///         let span = ctx.event_span(event).map(|span| span.name().to_string());
+///         let mut recorded = self.0.lock().unwrap();
+///         recorded.push(span.unwrap_or_else(|| "none".to_string()));
+///     }
+/// }
+/// fn main() {
+///     let recorded = Arc::new(Mutex::new(Vec::new()));
+///     let subscriber = Registry::default().with(SpanRecorder(Arc::clone(&recorded)));
+///     tracing::subscriber::set_global_default(subscriber).unwrap();
+///
+///     let pool = ThreadPool::build(2).unwrap();
+///     span!(tracing::Level::INFO, "request").in_scope(|| {
+///         pool.execute(|| tracing::info!("Running the job")).unwrap();
+///     });
+///     pool.execute(|| tracing::info!("Running outside of any span")).unwrap();
+///     pool.join();
+///
+///     let mut recorded = recorded.lock().unwrap().clone();
+///     recorded.sort();
+///     assert_eq!(recorded, ["none", "request"]);
+/// }
+/// ```
+#[cfg(feature = "tracing")]
+fn in_submission_span<'a>(job: Box<dyn FnOnce() + Send + 'a>) -> Box<dyn FnOnce() + Send + 'a> {
+    let span = tracing::Span::current();
+    Box::new(move || span.in_scope(job))
+}
+
+/// Spans require the `tracing` feature, so the job is left as is
+#[cfg(not(feature = "tracing"))]
+fn in_submission_span<'a>(job: Box<dyn FnOnce() + Send + 'a>) -> Box<dyn FnOnce() + Send + 'a> {
+    job
+}
+
+/// A guard counting a worker as blocked on the results of other jobs
+/// of its pool while it lives
+struct BlockedWorker<'a> {
+    shared: &'a Shared,
+}
+
+impl<'a> BlockedWorker<'a> {
+    fn new(shared: &'a Shared) -> BlockedWorker<'a> {
+        shared.blocked_workers.fetch_add(1, Ordering::SeqCst);
+        BlockedWorker { shared }
+    }
+}
+
+impl Drop for BlockedWorker<'_> {
+    fn drop(&mut self) {
+        self.shared.blocked_workers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A guard setting the label of the job the current worker runs,
+/// clearing it once dropped, even by a panicking job
+struct LabelGuard<'a> {
+    shared: &'a Shared,
+}
+
+impl<'a> LabelGuard<'a> {
+    fn new(shared: &'a Shared, label: &'static str) -> LabelGuard<'a> {
+        let entry = (current_worker_id(), label);
+        shared
+            .labels
+            .lock()
+            .unwrap()
+            .insert(thread::current().id(), entry);
+        LabelGuard { shared }
+    }
+}
+
+impl Drop for LabelGuard<'_> {
+    fn drop(&mut self) {
+        let mut labels = self.shared.labels.lock().unwrap();
+        labels.remove(&thread::current().id());
+    }
+}
+
+/// A guard recording the exit of a worker's thread when dropped,
+/// even if the thread is unwinding
+struct ExitGuard {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        let last = self.shared.live_workers.fetch_sub(1, Ordering::SeqCst) == 1;
+        if last && thread::panicking() {
+            self.shared.dead.store(true, Ordering::SeqCst);
+        }
+        self.shared.exited.lock().unwrap().push(self.id);
+        self.shared.worker_exited.notify_all();
+    }
+}
+
+thread_local! {
+    /// The shared state of the pool the current thread is a worker of,
+    /// or null if it is not a worker thread
+    static CURRENT_POOL: Cell<*const Shared> = const { Cell::new(std::ptr::null()) };
+
+    /// The ID of the worker running on the current thread, if any
+    static CURRENT_WORKER: Cell<Option<usize>> = const { Cell::new(None) };
+
+    /// Whether the panic hook installed by `install_quiet_panic_hook`
+    /// stays silent on the current thread
+    static PANIC_HOOK_SUPPRESSED: Cell<bool> = const { Cell::new(false) };
+
+    /// The pool installed on the current thread by `ThreadPool::install`
+    static INSTALLED_POOL: RefCell<Option<ThreadPool>> = const { RefCell::new(None) };
+
+    /// The values set up by `ThreadPoolBuilder::with_worker_local`
+    /// for the current worker thread, keyed by their `TypeId`
+    static WORKER_LOCALS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// Returns the ID of the worker running the current job
+///
+/// The jobs only run on worker threads, though not always the ones of
+/// the pool they were sent to, as the pools built from clones of
+/// a builder share its custom queue
+fn current_worker_id() -> usize {
+    CURRENT_WORKER
+        .with(Cell::get)
+        .expect("The jobs of a pool only run on worker threads")
+}
+
+/// Returns a handle to the pool installed on the current thread by
+/// `ThreadPool::install`, or `None` outside of `install`
+///
+/// # Examples
+///
+/// ```
+/// use threadpool::{current_pool, ThreadPool};
+/// fn main() {
+///     assert!(current_pool().is_none());
+///     let pool = ThreadPool::build(2).unwrap();
+///     pool.install(|| assert_eq!(current_pool().unwrap().thread_count(), 2));
+/// }
+/// ```
+pub fn current_pool() -> Option<ThreadPool> {
+    INSTALLED_POOL.with(|pool| pool.borrow().clone())
+}
+
+/// Wraps the process's panic hook, once, so that it stays silent
+/// on the threads that suppress it
+///
+/// The hook is process-wide, so it is wrapped instead of being swapped
+/// around each job, which would race with the other threads. The threads
+/// that do not suppress it still go through the previous hook
+fn install_quiet_panic_hook() {
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !PANIC_HOOK_SUPPRESSED.with(Cell::get) {
+                previous(info);
+            }
+        }));
+    });
+}
+
+/// A guard restoring the previously installed pool once `install` returns
+/// or unwinds
+struct InstallGuard {
+    previous: Option<ThreadPool>,
+}
+
+impl Drop for InstallGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        // The installed handle is dropped outside of the borrow, as
+        // dropping the last handle of a pool joins its workers
+        let installed = INSTALLED_POOL.with(|pool| pool.replace(previous));
+        drop(installed);
+    }
+}
+
+/// A struct representing a worker holding a thread for executing job
+pub struct Worker {
+    id: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Returns a new worker with its thread initiated
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The worker's ID
+    ///
+    /// * `shared` - The state shared with other workers within the same
+    ///   thread pool, including the lock-protected job queue
+    ///
+    /// * `thread_builder` - The configuration used to spawn the worker's thread,
+    ///   if the OS fails to spawn it, returns an `Err`
+    ///
+    /// # Caution
+    ///
+    /// A panicking job is caught so that the worker carries on with the
+    /// next job, but the panic can still leave the job's captured data
+    /// in an inconsistent state.
+    fn new(id: usize, shared: Arc<Shared>, thread_builder: thread::Builder) -> io::Result<Worker> {
+        // Counted before the thread starts, so that another worker dying
+        // meanwhile does not find the pool without live workers, which
+        // a resized dead pool has again
+        shared.live_workers.fetch_add(1, Ordering::SeqCst);
+        shared.dead.store(false, Ordering::SeqCst);
+        let counted = Arc::clone(&shared);
+        let thread = thread_builder.spawn(move || {
+            if shared.lifecycle_logging {
+                log::debug!("{} is starting up", shared.describe_worker(id));
+            }
+            let _exit_guard = ExitGuard {
+                id,
+                shared: Arc::clone(&shared),
+            };
+            CURRENT_POOL.with(|pool| pool.set(Arc::as_ptr(&shared)));
+            CURRENT_WORKER.with(|worker| worker.set(Some(id)));
+            if shared.panic_hook_suppression {
+                install_quiet_panic_hook();
+                PANIC_HOOK_SUPPRESSED.with(|suppressed| suppressed.set(true));
+            }
+            if !shared.core_ids.is_empty() {
+                let core_id = shared.core_ids[id % shared.core_ids.len()];
+                if pin_to_core(core_id) {
+                    log::debug!(
+                        "{} is pinned to core {}",
+                        shared.describe_worker(id),
+                        core_id
+                    );
+                } else {
+                    log::warn!(
+                        "{} could not be pinned to core {}",
+                        shared.describe_worker(id),
+                        core_id
+                    );
+                }
+            }
+            WORKER_LOCALS.with(|locals| {
+                let mut locals = locals.borrow_mut();
+                for (type_id, factory) in &shared.worker_locals {
+                    locals.insert(*type_id, factory(id));
+                }
+            });
+            if let Some(init) = &shared.worker_init {
+                init(id);
+            }
+            // The queue lock is released by `next_job` before running the job,
+            // otherwise the workers could only execute jobs one at a time
+            while let Some((job, detached)) = shared.next_job(id) {
+                if detached {
+                    // Only caught to keep the worker alive, the panic
+                    // is neither counted nor handled
+                    #[cfg(panic = "unwind")]
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    #[cfg(not(panic = "unwind"))]
+                    job();
+                    shared.finish_jobs(1);
+                    continue;
+                }
+                shared.active_count.fetch_add(1, Ordering::Relaxed);
+                let started = Instant::now();
+                // The jobs are not required to be `UnwindSafe`. Asserting it
+                // is sound, as `UnwindSafe` only guards against observing
+                // broken invariants, not memory unsafety: the job is consumed
+                // by the unwinding, and the state it shares with other threads
+                // is `Send + 'static`, e.g. behind a `Mutex`, which is poisoned
+                #[cfg(panic = "unwind")]
+                let result = panic::catch_unwind(AssertUnwindSafe(job));
+                // A panicking job aborts the process, catching it is a no-op
+                #[cfg(not(panic = "unwind"))]
+                let result: thread::Result<()> = {
+                    job();
+                    Ok(())
+                };
+                let busy = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+                shared.busy_nanos.fetch_add(busy, Ordering::Relaxed);
+                if let Err(payload) = result {
+                    shared.panic_count.fetch_add(1, Ordering::Relaxed);
+                    shared.log_panic(&*payload);
+                    if shared.panic_mode == PanicMode::Abort {
+                        shared.poison(payload);
+                    }
+                    if let Some(handler) = &shared.panic_handler {
+                        // A panicking handler must not take the worker down with it
+                        let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(payload)));
+                    }
+                }
+                shared.active_count.fetch_sub(1, Ordering::Relaxed);
+                // Counted before `finish_jobs`, so that it is seen by `join`
+                shared.completed_count.fetch_add(1, Ordering::Relaxed);
+                shared.finish_jobs(1);
+            }
+            if shared.lifecycle_logging {
+                log::debug!("{} is shutting down", shared.describe_worker(id));
+            }
+        });
+        let thread = thread.inspect_err(|_| {
+            counted.live_workers.fetch_sub(1, Ordering::SeqCst);
+        })?;
+
+        Ok(Worker {
+            id,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the worker's ID, unique within its pool
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns whether the worker's thread is still running
+    fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Waits for the worker's thread to finish, returns `false` if
+    /// the worker had panicked
+    fn try_join(&mut self) -> bool {
+        match self.thread.take() {
+            // The last handle of a pool may be dropped by one of its own
+            // jobs, in which case the worker cannot wait for itself
+            Some(thread) if thread.thread().id() != thread::current().id() => thread.join().is_ok(),
+            _ => true,
+        }
+    }
+
+    /// Waits for the worker's thread to finish
+    ///
+    /// # Panics
+    ///
+    /// If the worker had panicked, this method panics
+    fn join(&mut self) {
+        if !self.try_join() {
+            panic_on_dead_workers();
+        }
+    }
+}
+
+/// Reports that some workers have panicked, by panicking unless the current
+/// thread is already unwinding, e.g. from a poisoned pool, as panicking
+/// again would abort the process
+fn panic_on_dead_workers() {
+    if !thread::panicking() {
+        panic!(
+            "Warning: Some workers seem to have panicked. \
+             This likely has led to wrong behavior"
+        );
+    }
+}
+
+/// A snapshot of a worker's state, as returned by `ThreadPool::workers`
+///
+/// With the `serde` feature, it implements `Serialize` and `Deserialize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkerInfo {
+    /// The worker's ID
+    pub id: usize,
+    /// Whether the worker's thread has stopped running
+    pub is_finished: bool,
+}
+
+/// A snapshot of a pool's counters, as returned by `ThreadPool::metrics`
+///
+/// With the `serde` feature, it implements `Serialize` and `Deserialize`,
+/// e.g. to expose the counters as JSON
+#[cfg_attr(
+    feature = "serde",
+    doc = r##"
+# Examples
+
+```
+use threadpool::{PoolMetrics, ThreadPool, WorkerInfo};
+fn main() {
+    let pool = ThreadPool::build(1).unwrap();
+    pool.execute(|| {}).unwrap();
+    pool.join();
+
+    let json = serde_json::to_string(&pool.metrics()).unwrap();
+    assert_eq!(
+        json,
+        r#"{"thread_count":1,"queued_count":0,"active_count":0,"completed_count":1,"panic_count":0,"active_labels":[]}"#
+    );
+    assert_eq!(serde_json::from_str::<PoolMetrics>(&json).unwrap(), pool.metrics());
+
+    let workers: Vec<WorkerInfo> = pool.workers().collect();
+    let json = serde_json::to_string(&workers).unwrap();
+    assert_eq!(json, r#"[{"id":0,"is_finished":false}]"#);
+    assert_eq!(serde_json::from_str::<Vec<WorkerInfo>>(&json).unwrap(), workers);
+}
+```
+"##
+)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolMetrics {
+    /// The number of worker threads, see `ThreadPool::thread_count`
+    pub thread_count: usize,
+    /// The number of queued jobs, see `ThreadPool::queued_count`
+    pub queued_count: usize,
+    /// The number of running jobs, see `ThreadPool::active_count`
+    pub active_count: usize,
+    /// The number of finished jobs, see `ThreadPool::completed_count`
+    pub completed_count: usize,
+    /// The number of panicked jobs, see `ThreadPool::panic_count`
+    pub panic_count: usize,
+    /// The labels of the running jobs, see `ThreadPool::active_labels`
+    pub active_labels: Vec<String>,
+}
+
+/// The jobs left when a pool was shut down, as returned by
+/// `ThreadPool::shutdown`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    /// The number of queued jobs, which the workers executed
+    /// before shutting down
+    pub executed: usize,
+    /// The number of delayed jobs not yet due, which were dropped
+    /// without being run
+    pub discarded: usize,
+}
+
+/// A struct for configuring and building a `ThreadPool`
+///
+/// # Examples
+///
+/// ```
+/// use threadpool::ThreadPoolBuilder;
+/// fn main() {
+///     let pool = ThreadPoolBuilder::new()
+///         .num_threads(4)
+///         .thread_name(String::from("my-pool"))
+///         .stack_size(4 * 1024 * 1024)
+///         .build()
+///         .unwrap();
+///     pool.execute(|| println!("Hello from the pool")).unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ThreadPoolBuilder {
+    num_threads: usize,
+    name: Option<String>,
+    thread_name: Option<String>,
+    stack_size: Option<usize>,
+    panic_handler: Option<PanicHandler>,
+    panic_mode: PanicMode,
+    worker_init: Option<WorkerInit>,
+    worker_locals: Vec<LocalFactory>,
+    park_timeout: Option<Duration>,
+    maintenance: Option<WorkerMaintenance>,
+    queue_capacity: Option<usize>,
+    core_ids: Vec<usize>,
+    lifecycle_logging: bool,
+    panic_hook_suppression: bool,
+    autoscale: Option<(usize, usize)>,
+    job_queue: Option<CustomQueue>,
+    prealloc_jobs: usize,
+    drop_policy: DropPolicy,
+    lazy_spawn: bool,
+    auto_respawn: bool,
+    fail_spawn_at: Option<usize>,
+}
+
+impl fmt::Debug for ThreadPoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadPoolBuilder")
+            .field("num_threads", &self.num_threads)
+            .field("name", &self.name)
+            .field("thread_name", &self.thread_name)
+            .field("stack_size", &self.stack_size)
+            .field("panic_handler", &self.panic_handler.is_some())
+            .field("panic_mode", &self.panic_mode)
+            .field("worker_init", &self.worker_init.is_some())
+            .field("worker_locals", &self.worker_locals.len())
+            .field("park_timeout", &self.park_timeout)
+            .field("maintenance", &self.maintenance.is_some())
+            .field("queue_capacity", &self.queue_capacity)
+            .field("core_ids", &self.core_ids)
+            .field("lifecycle_logging", &self.lifecycle_logging)
+            .field("panic_hook_suppression", &self.panic_hook_suppression)
+            .field("autoscale", &self.autoscale)
+            .field("job_queue", &self.job_queue.is_some())
+            .field("prealloc_jobs", &self.prealloc_jobs)
+            .field("drop_policy", &self.drop_policy)
+            .field("lazy_spawn", &self.lazy_spawn)
+            .field("auto_respawn", &self.auto_respawn)
+            .field("fail_spawn_at", &self.fail_spawn_at)
+            .finish()
+    }
+}
+
+impl Default for ThreadPoolBuilder {
+    fn default() -> Self {
+        ThreadPoolBuilder::new()
+    }
+}
+
+impl ThreadPoolBuilder {
+    /// Returns a builder with the default configuration: one thread,
+    /// threads named `worker-{id}` and the OS default stack size
+    pub fn new() -> ThreadPoolBuilder {
+        ThreadPoolBuilder {
+            num_threads: 1,
+            name: None,
+            thread_name: None,
+            stack_size: None,
+            panic_handler: None,
+            panic_mode: PanicMode::Continue,
+            worker_init: None,
+            worker_locals: Vec::new(),
+            park_timeout: None,
+            maintenance: None,
+            queue_capacity: None,
+            core_ids: Vec::new(),
+            lifecycle_logging: false,
+            panic_hook_suppression: false,
+            autoscale: None,
+            job_queue: None,
+            prealloc_jobs: 0,
+            drop_policy: DropPolicy::Drain,
+            lazy_spawn: false,
+            auto_respawn: false,
+            fail_spawn_at: None,
+        }
+    }
+
+    /// Sets the number of threads in the pool
+    ///
+    /// # Arguments
+    ///
+    /// * `num_threads` - The number of threads, must be positive or else
+    ///   `build` returns an `Err`
+    ///
+    /// # Examples
+    ///
+    /// All four jobs can only pass the barrier if they run on four
+    /// different threads at the same time
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc, Barrier};
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    ///     let barrier = Arc::new(Barrier::new(4));
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for _ in 0..4 {
+    ///         let barrier = Arc::clone(&barrier);
+    ///         let sender = sender.clone();
+    ///         pool.execute(move || {
+    ///             barrier.wait();
+    ///             sender.send(()).unwrap();
+    ///         }).unwrap();
+    ///     }
+    ///     assert_eq!(receiver.iter().take(4).count(), 4);
+    /// }
+    /// ```
+    pub fn num_threads(mut self, num_threads: usize) -> ThreadPoolBuilder {
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// Sets the name prefix of the threads in the pool
+    ///
+    /// The thread of the worker with ID `id` is named `{prefix}-{id}`,
+    /// the default prefix is `worker`
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix of the threads' names
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .thread_name(String::from("my-pool"))
+    ///         .build()
+    ///         .unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || {
+    ///         let name = thread::current().name().map(String::from);
+    ///         sender.send(name).unwrap();
+    ///     }).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap().as_deref(), Some("my-pool-0"));
+    /// }
+    /// ```
+    ///
+    /// Without a prefix, the threads are named after their workers
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || {
+    ///         let name = thread::current().name().map(String::from);
+    ///         sender.send(name).unwrap();
+    ///     }).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap().as_deref(), Some("worker-0"));
+    /// }
+    /// ```
+    pub fn thread_name(mut self, prefix: String) -> ThreadPoolBuilder {
+        self.thread_name = Some(prefix);
+        self
+    }
+
+    /// Sets the name of the pool, telling it apart from the other pools
+    ///
+    /// The name is included in the log messages of the workers and,
+    /// unless `thread_name` sets another prefix, in their threads' names.
+    /// The default panic hook, like a panic handler calling
+    /// `std::thread::current`, thus reports which pool a panicking job
+    /// ran on. Pools are unnamed by default
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the pool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::{Log, Metadata, Record};
+    /// use std::sync::{mpsc, Mutex};
+    /// use std::thread;
+    /// use threadpool::ThreadPoolBuilder;
+    ///
+    /// struct CapturingLogger(Mutex<Vec<String>>);
+    ///
+    /// impl Log for CapturingLogger {
+    ///     fn enabled(&self, _: &Metadata) -> bool {
+    ///         true
+    ///     }
+    ///     fn log(&self, record: &Record) {
+    ///         self.0.lock().unwrap().push(record.args().to_string());
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    ///
+    /// fn main() {
+    ///     log::set_logger(&LOGGER).unwrap();
+    ///     log::set_max_level(log::LevelFilter::Trace);
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let pools: Vec<_> = ["db", "http"]
+    ///         .into_iter()
+    ///         .map(|name| {
+    ///             let sender = sender.clone();
+    ///             ThreadPoolBuilder::new()
+    ///                 .name(String::from(name))
+    ///                 .lifecycle_logging(true)
+    ///                 .panic_handler(move |_| {
+    ///                     let thread_name = thread::current().name().map(String::from);
+    ///                     sender.send(thread_name).unwrap();
+    ///                 })
+    ///                 .build()
+    ///                 .unwrap()
+    ///         })
+    ///         .collect();
+    ///     assert_eq!(pools[0].name(), Some("db"));
+    ///     assert_eq!(pools[1].name(), Some("http"));
+    ///
+    ///     pools[1].execute(|| panic!("Error"));
+    ///     assert_eq!(receiver.recv().unwrap().as_deref(), Some("http-0"));
+    ///
+    ///     drop(pools);
+    ///     let records = LOGGER.0.lock().unwrap();
+    ///     assert!(records.contains(&String::from("Thread 0 of pool db is starting up")));
+    ///     assert!(records.contains(&String::from("Thread 0 of pool http is shutting down")));
+    /// }
+    /// ```
+    pub fn name(mut self, name: String) -> ThreadPoolBuilder {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the stack size (in bytes) of the threads in the pool
+    ///
+    /// If not set, the OS default stack size is used, which may be too
+    /// small for jobs that recurse deeply or keep large arrays on the stack
+    ///
+    /// # Arguments
+    ///
+    /// * `stack_size` - The stack size in bytes
+    ///
+    /// # Examples
+    ///
+    /// A job using a 16 MiB buffer on its stack would overflow
+    /// a default-sized stack
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .stack_size(64 * 1024 * 1024)
+    ///         .build()
+    ///         .unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || {
+    ///         let buffer = std::hint::black_box([1u8; 16 * 1024 * 1024]);
+    ///         sender.send(buffer.iter().map(|&b| b as usize).sum::<usize>()).unwrap();
+    ///     }).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), 16 * 1024 * 1024);
+    /// }
+    /// ```
+    ///
+    /// Deeply recursive jobs also need a bigger stack than the default one
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn depth(n: usize) -> usize {
+    ///     let frame = std::hint::black_box([0u8; 1024]);
+    ///     if n == 0 {
+    ///         frame[0] as usize
+    ///     } else {
+    ///         1 + depth(n - 1) + std::hint::black_box(frame)[1] as usize
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .stack_size(256 * 1024 * 1024)
+    ///         .build()
+    ///         .unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || sender.send(depth(50_000)).unwrap()).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), 50_000);
+    /// }
+    /// ```
+    pub fn stack_size(mut self, stack_size: usize) -> ThreadPoolBuilder {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Sets the CPU cores the workers are pinned to, the worker with ID `id`
+    /// being pinned to `core_ids[id % core_ids.len()]` as it starts up
+    ///
+    /// Pinning requires the `core_affinity` feature. If it is disabled,
+    /// or the platform does not support it, a warning is logged and
+    /// the worker runs unpinned. An empty list disables pinning
+    ///
+    /// # Arguments
+    ///
+    /// * `core_ids` - The IDs of the cores, as numbered by the OS
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::{Level, Log, Metadata, Record};
+    /// use std::sync::Mutex;
+    /// use threadpool::ThreadPoolBuilder;
+    ///
+    /// struct CapturingLogger(Mutex<Vec<String>>);
+    ///
+    /// impl Log for CapturingLogger {
+    ///     fn enabled(&self, metadata: &Metadata) -> bool {
+    ///         metadata.level() <= Level::Debug
+    ///     }
+    ///     fn log(&self, record: &Record) {
+    ///         self.0.lock().unwrap().push(record.args().to_string());
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    ///
+    /// fn main() {
+    ///     log::set_logger(&LOGGER).unwrap();
+    ///     log::set_max_level(log::LevelFilter::Debug);
+    ///
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(3)
+    ///         .pin_to_cores(vec![0, 1])
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.shutdown();
+    ///
+    ///     // The core list wraps around, and whether each pin succeeded
+    ///     // depends on the platform and the enabled features
+    ///     let records = LOGGER.0.lock().unwrap();
+    ///     for (id, core_id) in [(0, 0), (1, 1), (2, 0)] {
+    ///         let pinned = format!("Thread {} is pinned to core {}", id, core_id);
+    ///         let failed = format!("Thread {} could not be pinned to core {}", id, core_id);
+    ///         assert!(records.iter().any(|record| *record == pinned || *record == failed));
+    ///     }
+    /// }
+    /// ```
+    pub fn pin_to_cores(mut self, core_ids: Vec<usize>) -> ThreadPoolBuilder {
+        self.core_ids = core_ids;
+        self
+    }
+
+    /// Sets whether the workers log their starting up and shutting down,
+    /// which they do not by default
+    ///
+    /// The messages are logged at the `Debug` level, see the `Logging`
+    /// section of `ThreadPool`. Disabling them removes them regardless
+    /// of the logger's configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether the lifecycle messages are logged
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::{Log, Metadata, Record};
+    /// use std::sync::Mutex;
+    /// use threadpool::ThreadPoolBuilder;
+    ///
+    /// struct CapturingLogger(Mutex<Vec<String>>);
+    ///
+    /// impl Log for CapturingLogger {
+    ///     fn enabled(&self, _: &Metadata) -> bool {
+    ///         true
+    ///     }
+    ///     fn log(&self, record: &Record) {
+    ///         self.0.lock().unwrap().push(record.args().to_string());
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    ///
+    /// fn main() {
+    ///     log::set_logger(&LOGGER).unwrap();
+    ///     log::set_max_level(log::LevelFilter::Trace);
+    ///
+    ///     drop(ThreadPoolBuilder::new().build().unwrap());
+    ///     assert!(LOGGER.0.lock().unwrap().is_empty());
+    ///
+    ///     drop(ThreadPoolBuilder::new().lifecycle_logging(true).build().unwrap());
+    ///     let records = LOGGER.0.lock().unwrap();
+    ///     assert_eq!(*records, vec!["Thread 0 is starting up", "Thread 0 is shutting down"]);
+    /// }
+    /// ```
+    pub fn lifecycle_logging(mut self, enabled: bool) -> ThreadPoolBuilder {
+        self.lifecycle_logging = enabled;
+        self
+    }
+
+    /// Returns the builder with the panic hook silenced on the workers'
+    /// threads or not, by default not
+    ///
+    /// The panics of the jobs are still caught and go through the pool's
+    /// panic handling, only the message and backtrace the hook prints
+    /// to stderr are left out
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether the panic hook is silenced
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::panic;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use threadpool::ThreadPoolBuilder;
+    ///
+    /// static HOOK_CALLS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// fn main() {
+    ///     panic::set_hook(Box::new(|_| {
+    ///         HOOK_CALLS.fetch_add(1, Ordering::SeqCst);
+    ///     }));
+    ///
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .panic_hook_suppression(true)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Expected")).unwrap();
+    ///     pool.join();
+    ///     assert_eq!(pool.take_panics(), ["Expected"]);
+    ///     assert_eq!(HOOK_CALLS.load(Ordering::SeqCst), 0);
+    ///
+    ///     // The other threads still go through the hook
+    ///     assert!(panic::catch_unwind(|| panic!("Unexpected")).is_err());
+    ///     assert_eq!(HOOK_CALLS.load(Ordering::SeqCst), 1);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// The panic hook is shared by the whole process, so the first pool
+    /// built with suppression wraps the hook set at that time, once and
+    /// for all. Setting another hook afterwards replaces the wrapper,
+    /// turning the suppression off for every pool
+    pub fn panic_hook_suppression(mut self, enabled: bool) -> ThreadPoolBuilder {
+        self.panic_hook_suppression = enabled;
+        self
+    }
+
+    /// Lets the pool resize itself between `min` and `max` threads
+    /// depending on its load
+    ///
+    /// A monitor thread, named after the workers with a `-monitor` suffix,
+    /// samples the pool every 50 milliseconds. Once jobs have been queued
+    /// for two samples in a row, the pool grows by the number of queued
+    /// jobs, up to `max` threads. Once some workers have been idle for
+    /// a second, the pool shrinks to the number of busy workers, down to
+    /// `min` threads. The monitor stops once the pool is shut down or dropped
+    ///
+    /// The pool starts with the configured number of threads, clamped
+    /// between `min` and `max`. `min` is at least 1, and `max` at least `min`
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The minimum number of threads
+    ///
+    /// * `max` - The maximum number of threads
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::{Duration, Instant};
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(1)
+    ///         .autoscale(1, 4)
+    ///         .build()
+    ///         .unwrap();
+    ///     for _ in 0..100 {
+    ///         pool.execute(|| thread::sleep(Duration::from_millis(20))).unwrap();
+    ///     }
+    ///
+    ///     let deadline = Instant::now() + Duration::from_secs(10);
+    ///     while pool.thread_count() < 4 && Instant::now() < deadline {
+    ///         thread::sleep(Duration::from_millis(10));
+    ///     }
+    ///     assert_eq!(pool.thread_count(), 4);
+    ///
+    ///     pool.join();
+    ///     let deadline = Instant::now() + Duration::from_secs(10);
+    ///     while pool.thread_count() > 1 && Instant::now() < deadline {
+    ///         thread::sleep(Duration::from_millis(10));
+    ///     }
+    ///     assert_eq!(pool.thread_count(), 1);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// The monitor keeps resizing the pool after `ThreadPool::set_num_threads`
+    /// has been called, so its effect only lasts until the next resize
+    pub fn autoscale(mut self, min: usize, max: usize) -> ThreadPoolBuilder {
+        let min = min.max(1);
+        self.autoscale = Some((min, max.max(min)));
+        self
+    }
+
+    /// Sets a handler invoked on the worker's thread with the payload
+    /// of every panicking job
+    ///
+    /// If the handler itself panics, the panic is caught and the worker
+    /// carries on with the next job
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - A callable implementing
+    ///   `Fn(Box<dyn Any + Send>) + Send + Sync + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .panic_handler(move |payload| {
+    ///             let message = payload.downcast_ref::<&str>().map(|s| s.to_string());
+    ///             sender.send(message).unwrap();
+    ///         })
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("boom")).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap().as_deref(), Some("boom"));
+    /// }
+    /// ```
+    ///
+    /// A panicking handler does not kill the worker
+    ///
+    /// ```
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .panic_handler(|_| panic!("Handler error"))
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     assert_eq!(pool.submit(|| 42).recv().unwrap(), 42);
+    /// }
+    /// ```
+    pub fn panic_handler<F>(mut self, handler: F) -> ThreadPoolBuilder
+    where
+        F: Fn(Box<dyn Any + Send>) + Send + Sync + 'static,
+    {
+        self.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets how the workers react to a panicking job, defaults to
+    /// `PanicMode::Continue`
+    ///
+    /// In `PanicMode::Abort`, the panic handler is not invoked, the panic
+    /// instead propagating to the threads using the pool
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The `PanicMode` of the pool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .panic_mode(PanicMode::Continue)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     assert_eq!(pool.submit(|| 42).recv().unwrap(), 42);
+    /// }
+    /// ```
+    ///
+    /// A panicking job kills an aborting pool, which then panics
+    /// when it is used
+    ///
+    /// ```should_panic
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     pool.join();
+    /// }
+    /// ```
+    pub fn panic_mode(mut self, mode: PanicMode) -> ThreadPoolBuilder {
+        self.panic_mode = mode;
+        self
+    }
+
+    /// Returns the builder with what dropping the last handle of the pool
+    /// does with the queued jobs, by default `DropPolicy::Drain`
+    ///
+    /// `shutdown` always drains the queue, call `clear_queue` beforehand
+    /// to abandon the jobs
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The `DropPolicy` applied when the pool is dropped
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{DropPolicy, ThreadPoolBuilder};
+    /// fn main() {
+    ///     for (policy, expected) in [(DropPolicy::Drain, 10), (DropPolicy::Abandon, 0)] {
+    ///         let pool = ThreadPoolBuilder::new().drop_policy(policy).build().unwrap();
+    ///         let (started_sender, started) = mpsc::channel();
+    ///         pool.execute(move || {
+    ///             started_sender.send(()).unwrap();
+    ///             thread::sleep(Duration::from_millis(200));
+    ///         }).unwrap();
+    ///         started.recv().unwrap();
+    ///
+    ///         let counter = Arc::new(AtomicUsize::new(0));
+    ///         for _ in 0..10 {
+    ///             let counter = Arc::clone(&counter);
+    ///             pool.execute(move || {
+    ///                 counter.fetch_add(1, Ordering::SeqCst);
+    ///             }).unwrap();
+    ///         }
+    ///
+    ///         // The running job finishes while the pool is being dropped
+    ///         drop(pool);
+    ///         assert_eq!(counter.load(Ordering::SeqCst), expected);
+    ///     }
+    /// }
+    /// ```
+    pub fn drop_policy(mut self, policy: DropPolicy) -> ThreadPoolBuilder {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Sets a callable invoked once on every worker's thread with the
+    /// worker's ID, before the worker picks up its first job
+    ///
+    /// As it runs on the worker's thread, the thread-locals it sets
+    /// persist for the jobs executed by that worker. It also runs for
+    /// the workers spawned by `set_num_threads`
+    ///
+    /// # Arguments
+    ///
+    /// * `init` - A callable implementing `Fn(usize) + Send + Sync + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use threadpool::ThreadPoolBuilder;
+    /// thread_local! {
+    ///     static WORKER_ID: Cell<Option<usize>> = const { Cell::new(None) };
+    /// }
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .with_worker_init(|id| WORKER_ID.with(|cell| cell.set(Some(id))))
+    ///         .build()
+    ///         .unwrap();
+    ///     let id = pool.submit(|| WORKER_ID.with(|cell| cell.get()));
+    ///     assert_eq!(id.recv().unwrap(), Some(0));
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// Unlike a job, a panicking init is not caught and terminates the worker,
+    /// making the pool panic when it is shut down
+    pub fn with_worker_init<F>(mut self, init: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.worker_init = Some(Arc::new(init));
+        self
+    }
+
+    /// Sets how long an idle worker waits for a job before running the
+    /// maintenance set by `with_maintenance`, then waiting again
+    ///
+    /// With `None`, the default, idle workers wait for a job without
+    /// ever waking up on their own
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long an idle worker waits between two
+    ///   maintenance runs
+    ///
+    /// # Examples
+    ///
+    /// See `with_maintenance`
+    pub fn park_timeout(mut self, timeout: Option<Duration>) -> ThreadPoolBuilder {
+        self.park_timeout = timeout;
+        self
+    }
+
+    /// Sets a callable invoked on an idle worker's thread with the worker's
+    /// ID, every time it has waited for a job for the park timeout, e.g.
+    /// to flush the metrics it has buffered
+    ///
+    /// It only runs if a timeout is set by `park_timeout`. If it panics,
+    /// the panic is caught and the worker waits for a job again
+    ///
+    /// # Arguments
+    ///
+    /// * `maintenance` - A callable implementing `Fn(usize) + Send + Sync + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let runs = Arc::new(AtomicUsize::new(0));
+    ///     let maintenance_runs = Arc::clone(&runs);
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .park_timeout(Some(Duration::from_millis(10)))
+    ///         .with_maintenance(move |_| {
+    ///             maintenance_runs.fetch_add(1, Ordering::SeqCst);
+    ///         })
+    ///         .build()
+    ///         .unwrap();
+    ///
+    ///     while runs.load(Ordering::SeqCst) < 3 {
+    ///         thread::sleep(Duration::from_millis(5));
+    ///     }
+    ///     assert_eq!(pool.submit(|| 42).recv().unwrap(), 42);
+    /// }
+    /// ```
+    pub fn with_maintenance<F>(mut self, maintenance: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.maintenance = Some(Arc::new(maintenance));
+        self
+    }
+
+    /// Sets a factory producing a value of type `L` owned by every worker,
+    /// which jobs sent through `ThreadPool::execute_with_local` can mutate
+    ///
+    /// The factory is called on every worker's thread with the worker's ID
+    /// as it starts up, before the init set by `with_worker_init`. Several
+    /// factories can be set as long as their types differ, a second factory
+    /// of the same type replacing the first one
+    ///
+    /// # Arguments
+    ///
+    /// * `factory` - A callable implementing `Fn(usize) -> L + Send + Sync + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .with_worker_local(|_| Vec::<u8>::with_capacity(1024))
+    ///         .build()
+    ///         .unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for i in 0..3 {
+    ///         let sender = sender.clone();
+    ///         pool.execute_with_local(move |buffer: &mut Vec<u8>| {
+    ///             buffer.clear();
+    ///             buffer.extend_from_slice(&[i; 512]);
+    ///             sender.send((buffer.as_ptr() as usize, buffer.capacity())).unwrap();
+    ///         });
+    ///     }
+    ///     drop(sender);
+    ///
+    ///     // The only worker reuses the same allocation for every job
+    ///     let buffers: Vec<_> = receiver.iter().collect();
+    ///     assert!(buffers.iter().all(|&buffer| buffer == buffers[0]));
+    ///     assert_eq!(buffers[0].1, 1024);
+    /// }
+    /// ```
+    pub fn with_worker_local<L, G>(mut self, factory: G) -> ThreadPoolBuilder
+    where
+        L: 'static,
+        G: Fn(usize) -> L + Send + Sync + 'static,
+    {
+        let type_id = TypeId::of::<L>();
+        self.worker_locals.retain(|(id, _)| *id != type_id);
+        self.worker_locals
+            .push((type_id, Arc::new(move |id| Box::new(factory(id)))));
+        self
+    }
+
+    /// Bounds the number of jobs waiting in the queue, so that
+    /// producers are slowed down instead of the queue growing unbounded
+    ///
+    /// Once the queue is full, `execute` blocks until a worker picks up
+    /// a job, unless called from a job of the pool, whereas `try_execute`
+    /// returns an `Err`. The jobs being
+    /// executed do not count towards the capacity, and a capacity of 0
+    /// is treated as 1
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of queued jobs
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::{JobRejected, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new().queue_capacity(1).build().unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///
+    ///     // The only worker is busy, so the second job fills the queue
+    ///     pool.execute(|| {}).unwrap();
+    ///     assert!(matches!(pool.try_execute(|| {}), Err(JobRejected::Full(_))));
+    ///
+    ///     release.send(()).unwrap();
+    ///     pool.join();
+    ///     assert!(pool.try_execute(|| {}).is_ok());
+    /// }
+    /// ```
+    ///
+    /// A blocked `execute` returns once a worker frees a slot
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new().queue_capacity(1).build().unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///     pool.execute(|| {}).unwrap();
+    ///
+    ///     let submitted = Arc::new(AtomicBool::new(false));
+    ///     let producer = {
+    ///         let pool = pool.clone();
+    ///         let submitted = Arc::clone(&submitted);
+    ///         thread::spawn(move || {
+    ///             pool.execute(|| {}).unwrap();
+    ///             submitted.store(true, Ordering::SeqCst);
+    ///         })
+    ///     };
+    ///
+    ///     thread::sleep(Duration::from_millis(50));
+    ///     assert!(!submitted.load(Ordering::SeqCst));
+    ///
+    ///     release.send(()).unwrap();
+    ///     producer.join().unwrap();
+    ///     assert!(submitted.load(Ordering::SeqCst));
+    /// }
+    /// ```
+    pub fn queue_capacity(mut self, capacity: usize) -> ThreadPoolBuilder {
+        self.queue_capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Returns the builder with a custom queue the workers pop their
+    /// jobs from, instead of the pool's local queues
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The queue implementing `JobQueue`, deciding the order
+    ///   the jobs are run in
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc, Mutex};
+    /// use threadpool::{Job, JobQueue, ThreadPoolBuilder};
+    /// struct LifoQueue(Mutex<Vec<Job>>);
+    ///
+    /// impl JobQueue for LifoQueue {
+    ///     fn push(&self, job: Job) {
+    ///         self.0.lock().unwrap().push(job);
+    ///     }
+    ///
+    ///     fn pop(&self) -> Option<Job> {
+    ///         self.0.lock().unwrap().pop()
+    ///     }
+    /// }
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .job_queue(Box::new(LifoQueue(Mutex::new(Vec::new()))))
+    ///         .build()
+    ///         .unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///
+    ///     let order = Arc::new(Mutex::new(Vec::new()));
+    ///     for i in 0..4 {
+    ///         let order = Arc::clone(&order);
+    ///         pool.execute(move || order.lock().unwrap().push(i)).unwrap();
+    ///     }
+    ///     release.send(()).unwrap();
+    ///     pool.join();
+    ///     assert_eq!(*order.lock().unwrap(), [3, 2, 1, 0]);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// The pools built from clones of this builder share the queue.
+    /// `restart` shuts the previous pool down before building a new one
+    /// for that reason
+    pub fn job_queue(mut self, queue: Box<dyn JobQueue + Send + Sync>) -> ThreadPoolBuilder {
+        self.job_queue = Some(Arc::from(queue));
+        self
+    }
+
+    /// Returns the builder with room for `count` queued jobs allocated
+    /// upfront, so that a known burst of jobs does not grow the queues
+    /// while it is being sent
+    ///
+    /// The room is shared between the local queues of the workers the pool
+    /// is built with. It is only a hint: more jobs can still be queued,
+    /// and it is ignored when a custom job queue is set
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of jobs to allocate room for
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use threadpool::{ThreadPool, ThreadPoolBuilder};
+    /// struct CountingAllocator;
+    ///
+    /// static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// unsafe impl GlobalAlloc for CountingAllocator {
+    ///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ///         ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    ///         System.alloc(layout)
+    ///     }
+    ///
+    ///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ///         System.dealloc(ptr, layout)
+    ///     }
+    ///
+    ///     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+    ///         ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    ///         System.realloc(ptr, layout, size)
+    ///     }
+    /// }
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: CountingAllocator = CountingAllocator;
+    ///
+    /// /// Returns the allocations made while queueing a burst of jobs
+    /// fn allocations_for_burst(pool: &ThreadPool) -> usize {
+    ///     // Paused, so that the whole burst stays queued
+    ///     pool.pause();
+    ///     let before = ALLOCATIONS.load(Ordering::SeqCst);
+    ///     for _ in 0..10_000 {
+    ///         pool.execute(|| {}).unwrap();
+    ///     }
+    ///     let allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+    ///     pool.resume();
+    ///     pool.join();
+    ///     allocations
+    /// }
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    ///     let without_hint = allocations_for_burst(&pool);
+    ///
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .prealloc_jobs(10_000)
+    ///         .build()
+    ///         .unwrap();
+    ///     let with_hint = allocations_for_burst(&pool);
+    ///     assert!(with_hint < without_hint, "{} < {}", with_hint, without_hint);
+    /// }
+    /// ```
+    pub fn prealloc_jobs(mut self, count: usize) -> ThreadPoolBuilder {
+        self.prealloc_jobs = count;
+        self
+    }
+
+    /// Sets whether the workers are spawned as jobs arrive rather than
+    /// all at once by `build`, defaults to `false`
+    ///
+    /// A lazily spawning pool starts without any thread. Submitting a job
+    /// spawns a worker unless enough workers are sleeping to pick up the
+    /// queued jobs, up to the configured number of threads. The spawned
+    /// workers are kept until the pool is shut down or resized
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether the workers are spawned on demand
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Barrier};
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(4)
+    ///         .lazy_spawn(true)
+    ///         .build()
+    ///         .unwrap();
+    ///     assert_eq!(pool.thread_count(), 0);
+    ///     assert_eq!(pool.workers().count(), 0);
+    ///
+    ///     // The four jobs can only pass the barrier together
+    ///     // once four workers have been spawned
+    ///     let barrier = Arc::new(Barrier::new(4));
+    ///     for _ in 0..4 {
+    ///         let barrier = Arc::clone(&barrier);
+    ///         pool.execute(move || {
+    ///             barrier.wait();
+    ///         }).unwrap();
+    ///     }
+    ///     pool.join();
+    ///     assert_eq!(pool.thread_count(), 4);
+    ///
+    ///     for _ in 0..100 {
+    ///         pool.execute(|| {}).unwrap();
+    ///     }
+    ///     pool.join();
+    ///     assert_eq!(pool.thread_count(), 4);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// `ThreadPool::execute_on` only accepts jobs for the workers spawned
+    /// so far, and `ThreadPool::set_num_threads` still spawns the added
+    /// workers right away
+    pub fn lazy_spawn(mut self, enabled: bool) -> ThreadPoolBuilder {
+        self.lazy_spawn = enabled;
+        self
+    }
+
+    /// Sets whether the workers that die are replaced automatically,
+    /// defaults to `false`
+    ///
+    /// A supervisor thread, named after the workers with a `-supervisor`
+    /// suffix, looks for dead workers every 50 milliseconds, e.g. after
+    /// a job panicked in `PanicMode::Abort`. It recovers the locks poisoned
+    /// by the panic and spawns a worker with the same ID for each dead one,
+    /// which picks up the jobs left in the queue. Unlike
+    /// `ThreadPool::restart`, the pool and its counters are kept, so every
+    /// handle of the pool heals. The supervisor stops once the pool is shut
+    /// down or dropped
+    ///
+    /// Respawns are at least 100 milliseconds apart. Each time workers die
+    /// again shortly after being respawned, e.g. from a panicking worker
+    /// init, that delay doubles, up to 10 seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether the dead workers are respawned
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use std::thread;
+    /// use std::time::{Duration, Instant};
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let spawned = Arc::new(AtomicUsize::new(0));
+    ///     let counter = Arc::clone(&spawned);
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .with_worker_init(move |_| {
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///         })
+    ///         .auto_respawn(true)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///
+    ///     let deadline = Instant::now() + Duration::from_secs(10);
+    ///     while (spawned.load(Ordering::SeqCst) <= 2 || !pool.is_healthy())
+    ///         && Instant::now() < deadline
+    ///     {
+    ///         thread::sleep(Duration::from_millis(10));
+    ///     }
+    ///     assert!(spawned.load(Ordering::SeqCst) > 2);
+    ///     let live = pool.workers().filter(|worker| !worker.is_finished).count();
+    ///     assert_eq!(live, 2);
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || sender.send("Recovered").unwrap()).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), "Recovered");
+    ///     pool.join();
+    ///     assert_eq!(pool.active_count(), 0);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// Until the dead workers have been respawned, using the pool still
+    /// panics as described by `PanicMode::Abort`, and the jobs running on
+    /// the workers that survived the panic are not interrupted
+    pub fn auto_respawn(mut self, enabled: bool) -> ThreadPoolBuilder {
+        self.auto_respawn = enabled;
+        self
+    }
+
+    /// Makes `build` fail to spawn the worker with ID `id`, so that
+    /// the rollback of the workers spawned before it can be tested
+    #[doc(hidden)]
+    pub fn fail_spawn_at(mut self, id: usize) -> ThreadPoolBuilder {
+        self.fail_spawn_at = Some(id);
+        self
+    }
+
+    /// Returns a `Result<ThreadPool, PoolError>` built from
+    /// the configuration
+    ///
+    /// If the thread count is not positive or a worker thread can not
+    /// be spawned, returns an `Err`. In the latter case, the workers
+    /// spawned so far are shut down and joined before returning
+    ///
+    /// # Examples
+    ///
+    /// This code panics
+    ///
+    /// ```should_panic
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     ThreadPoolBuilder::new().num_threads(0).build().unwrap();
+    /// }
+    /// ```
+    ///
+    /// The OS refusing to spawn a thread, here because of an impossible
+    /// stack size, is reported rather than panicking
+    ///
+    /// ```
+    /// use threadpool::{PoolError, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let result = ThreadPoolBuilder::new()
+    ///         .num_threads(4)
+    ///         .stack_size(usize::MAX)
+    ///         .build();
+    ///     assert!(matches!(result, Err(PoolError::SpawnFailed(_))));
+    /// }
+    /// ```
+    ///
+    /// When the third of four workers fails to spawn, the first two
+    /// have started up and are joined before the `Err` is returned
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::{PoolError, ThreadPoolBuilder};
+    /// struct ExitCounter(Arc<AtomicUsize>);
+    /// impl Drop for ExitCounter {
+    ///     fn drop(&mut self) {
+    ///         self.0.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// }
+    /// fn main() {
+    ///     let started = Arc::new(AtomicUsize::new(0));
+    ///     let exited = Arc::new(AtomicUsize::new(0));
+    ///     let (init_started, local_exited) = (Arc::clone(&started), Arc::clone(&exited));
+    ///     let result = ThreadPoolBuilder::new()
+    ///         .num_threads(4)
+    ///         // Dropped as the worker's thread exits
+    ///         .with_worker_local(move |_| ExitCounter(Arc::clone(&local_exited)))
+    ///         .with_worker_init(move |_| {
+    ///             init_started.fetch_add(1, Ordering::SeqCst);
+    ///         })
+    ///         .fail_spawn_at(2)
+    ///         .build();
+    ///     assert!(matches!(result, Err(PoolError::SpawnFailed(_))));
+    ///     assert_eq!(started.load(Ordering::SeqCst), 2);
+    ///     assert_eq!(exited.load(Ordering::SeqCst), 2);
+    /// }
+    /// ```
+    #[must_use = "dropping the built pool shuts it down right away"]
+    pub fn build(mut self) -> Result<ThreadPool, PoolError> {
+        if self.num_threads == 0 {
+            return Err(PoolError::InvalidThreadCount(self.num_threads));
+        }
+        if let Some((min, max)) = self.autoscale {
+            self.num_threads = self.num_threads.clamp(min, max);
+        }
+
+        let shared = Arc::new(Shared::new(&self, self.num_threads));
+
+        let mut workers = Vec::with_capacity(self.num_threads);
+
+        // A lazily spawning pool starts without any worker
+        let eager = if self.lazy_spawn { 0 } else { self.num_threads };
+        for id in 0..eager {
+            let spawned = if self.fail_spawn_at == Some(id) {
+                Err(io::Error::other("The spawn was made to fail"))
+            } else {
+                Worker::new(id, shared.clone(), self.thread_builder(id))
+            };
+            match spawned {
+                Ok(worker) => workers.push(worker),
+                Err(err) => {
+                    // The workers spawned so far would otherwise wait
+                    // for jobs forever
+                    shared.close();
+                    for worker in &mut workers {
+                        worker.try_join();
+                    }
+                    return Err(PoolError::SpawnFailed(err));
+                }
+            }
+        }
+
+        let autoscale = self.autoscale;
+        let pool = ThreadPool {
+            inner: Arc::new(Inner {
+                shared,
+                thread_count: AtomicUsize::new(workers.len()),
+                next_id: AtomicUsize::new(workers.len()),
+                workers: Mutex::new(workers),
+                spawn_limit: AtomicUsize::new(self.num_threads),
+                builder: self,
+                scheduler: Arc::new(Scheduler {
+                    timers: Mutex::new(Timers {
+                        jobs: BinaryHeap::new(),
+                        next_seq: 0,
+                        closed: false,
+                    }),
+                    timer_changed: Condvar::new(),
+                }),
+                scheduler_thread: Mutex::new(None),
+                monitor: Arc::new(Monitor {
+                    stopped: Mutex::new(false),
+                    stop: Condvar::new(),
+                }),
+                monitor_thread: Mutex::new(None),
+                supervisor_thread: Mutex::new(None),
+                next_group: AtomicUsize::new(0),
+            }),
+        };
+
+        if pool.inner.builder.lazy_spawn {
+            let _ = pool.inner.shared.on_demand.set(Arc::downgrade(&pool.inner));
+        }
+
+        if let Some((min, max)) = autoscale {
+            let monitor = Arc::clone(&pool.inner.monitor);
+            let weak = Arc::downgrade(&pool.inner);
+            let thread = thread::Builder::new()
+                .name(format!("{}-monitor", pool.inner.builder.thread_prefix()))
+                .spawn(move || monitor.run(weak, min, max))
+                .map_err(PoolError::SpawnFailed)?;
+            *pool.inner.monitor_thread.lock().unwrap() = Some(thread);
+        }
+
+        if pool.inner.builder.auto_respawn {
+            let monitor = Arc::clone(&pool.inner.monitor);
+            let weak = Arc::downgrade(&pool.inner);
+            let thread = thread::Builder::new()
+                .name(format!("{}-supervisor", pool.inner.builder.thread_prefix()))
+                .spawn(move || monitor.supervise(weak))
+                .map_err(PoolError::SpawnFailed)?;
+            *pool.inner.supervisor_thread.lock().unwrap() = Some(thread);
+        }
+        Ok(pool)
+    }
+
+    /// Returns the prefix of the threads' names: the one set by
+    /// `thread_name`, or else the name of the pool, or else `worker`
+    fn thread_prefix(&self) -> &str {
+        self.thread_name
+            .as_deref()
+            .or(self.name.as_deref())
+            .unwrap_or("worker")
+    }
+
+    /// Returns the `thread::Builder` used to spawn the worker with ID `id`
+    fn thread_builder(&self, id: usize) -> thread::Builder {
+        let mut builder = thread::Builder::new().name(format!("{}-{}", self.thread_prefix(), id));
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        builder
+    }
+}
+
+/// A struct representing a thread pool
+///
+/// # Logging
+///
+/// Once enabled by `ThreadPoolBuilder::lifecycle_logging`, the workers'
+/// lifecycle events are logged at the `Debug` level through the `log`
+/// facade, so they are silent unless a logger is installed
+///
+/// ```
+/// use log::{Level, Log, Metadata, Record};
+/// use std::sync::Mutex;
+/// use threadpool::ThreadPoolBuilder;
+///
+/// struct CapturingLogger(Mutex<Vec<(Level, String)>>);
+///
+/// impl Log for CapturingLogger {
+///     fn enabled(&self, _: &Metadata) -> bool {
+///         true
+///     }
+///     fn log(&self, record: &Record) {
+///         let message = record.args().to_string();
+///         self.0.lock().unwrap().push((record.level(), message));
+///     }
+///     fn flush(&self) {}
+/// }
+///
+/// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+///
+/// fn main() {
+///     log::set_logger(&LOGGER).unwrap();
+///     log::set_max_level(log::LevelFilter::Trace);
+///
+///     drop(ThreadPoolBuilder::new().lifecycle_logging(true).build().unwrap());
+///
+///     let records = LOGGER.0.lock().unwrap();
+///     assert!(records.contains(&(Level::Debug, String::from("Thread 0 is starting up"))));
+///     assert!(records.contains(&(Level::Debug, String::from("Thread 0 is shutting down"))));
+/// }
+/// ```
+///
+/// # Scheduling
+///
+/// Every worker owns a queue. `execute` pushes the job to the least loaded
+/// queue, and a worker whose queue is empty steals the jobs of the others,
+/// so that the workers rarely contend on the same lock
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::thread;
+/// use threadpool::ThreadPool;
+/// fn main() {
+///     let pool = ThreadPool::build(8).unwrap();
+///     let runs: Arc<Vec<AtomicUsize>> = Arc::new((0..4000).map(|_| AtomicUsize::new(0)).collect());
+///
+///     let producers: Vec<_> = (0..4)
+///         .map(|producer| {
+///             let pool = pool.clone();
+///             let runs = Arc::clone(&runs);
+///             thread::spawn(move || {
+///                 for i in (producer * 1000)..((producer + 1) * 1000) {
+///                     let runs = Arc::clone(&runs);
+///                     pool.execute(move || {
+///                         runs[i].fetch_add(1, Ordering::SeqCst);
+///                     }).unwrap();
+///                 }
+///             })
+///         })
+///         .collect();
+///     for producer in producers {
+///         producer.join().unwrap();
+///     }
+///     pool.join();
+///
+///     // Every job ran exactly once
+///     assert!(runs.iter().all(|count| count.load(Ordering::SeqCst) == 1));
+/// }
+/// ```
+///
+/// # Sharing
+///
+/// Cloning a `ThreadPool` returns another handle to the same pool,
+/// which is only shut down once every handle has been dropped
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::thread;
+/// use threadpool::ThreadPool;
+/// fn main() {
+///     let pool = ThreadPool::build(4).unwrap();
+///     let counter = Arc::new(AtomicUsize::new(0));
+///
+///     let handles: Vec<_> = (0..4)
+///         .map(|_| {
+///             let pool = pool.clone();
+///             let counter = Arc::clone(&counter);
+///             thread::spawn(move || {
+///                 for _ in 0..25 {
+///                     let counter = Arc::clone(&counter);
+///                     pool.execute(move || {
+///                         counter.fetch_add(1, Ordering::SeqCst);
+///                     }).unwrap();
+///                 }
+///                 // Each thread drops its own clone, in no particular order
+///             })
+///         })
+///         .collect();
+///     for handle in handles {
+///         handle.join().unwrap();
+///     }
+///
+///     // The last handle joins the workers, so every job has finished
+///     drop(pool);
+///     assert_eq!(counter.load(Ordering::SeqCst), 100);
+/// }
+/// ```
+///
+/// `ThreadPool` is also `Sync` and `execute` takes `&self`, so a borrowed
+/// pool can be shared between threads without cloning it
+///
+/// ```
+/// use std::sync::mpsc;
+/// use std::thread;
+/// use threadpool::ThreadPool;
+/// fn main() {
+///     let pool = ThreadPool::build(4).unwrap();
+///     let (sender, receiver) = mpsc::channel();
+///     thread::scope(|scope| {
+///         for i in 0..4 {
+///             let (pool, sender) = (&pool, sender.clone());
+///             scope.spawn(move || pool.execute(move || sender.send(i).unwrap()).unwrap());
+///         }
+///     });
+///     drop(sender);
+///     assert_eq!(receiver.iter().sum::<i32>(), 6);
+/// }
+/// ```
+///
+/// # Discarding
+///
+/// Dropping the last handle shuts the pool down, so a pool that is built
+/// and discarded right away triggers the `unused_must_use` lint
+///
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// use threadpool::{PoolError, ThreadPool};
+/// fn main() -> Result<(), PoolError> {
+///     ThreadPool::build(4)?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+#[must_use = "dropping the last handle of a pool shuts it down"]
+pub struct ThreadPool {
+    inner: Arc<Inner>,
+}
+
+/// The state shared between the handles of a thread pool
+struct Inner {
+    shared: Arc<Shared>,
+    workers: Mutex<Vec<Worker>>,
+    /// The length of `workers`, readable while the pool is being resized
+    thread_count: AtomicUsize,
+    /// The ID of the next worker to be spawned
+    next_id: AtomicUsize,
+    /// The number of threads the pool has been sized to, up to which
+    /// a lazily spawning pool spawns its workers
+    spawn_limit: AtomicUsize,
+    /// The configuration the pool was built with, used to spawn new workers
+    builder: ThreadPoolBuilder,
+    scheduler: Arc<Scheduler>,
+    /// The thread running the delayed jobs, spawned on first use
+    scheduler_thread: Mutex<Option<JoinHandle<()>>>,
+    monitor: Arc<Monitor>,
+    /// The thread resizing the pool, if it autoscales
+    monitor_thread: Mutex<Option<JoinHandle<()>>>,
+    /// The thread respawning the dead workers, if the pool respawns them
+    supervisor_thread: Mutex<Option<JoinHandle<()>>>,
+    /// The rotating index of the worker the next `SequentialGroup` runs on
+    next_group: AtomicUsize,
+}
+
+impl Inner {
+    /// Spawns a worker unless the pool is closed or already has
+    /// `spawn_limit` workers, returns its ID
+    ///
+    /// A worker failing to spawn is logged, the pushed jobs being left
+    /// to the other workers
+    fn spawn_lazily(&self, workers: &mut Vec<Worker>) -> Option<usize> {
+        if self.shared.is_closed() || workers.len() >= self.spawn_limit.load(Ordering::Relaxed) {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        match Worker::new(id, self.shared.clone(), self.builder.thread_builder(id)) {
+            Ok(worker) => {
+                workers.push(worker);
+                self.thread_count.store(workers.len(), Ordering::Relaxed);
+                Some(id)
+            }
+            Err(err) => {
+                log::warn!("A worker could not be spawned on demand: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Closes the queue, so that the workers shut down once it is drained,
+    /// and shuts the scheduler down, dropping the jobs not yet due, along
+    /// with the autoscaling monitor and the supervisor
+    ///
+    /// Returns how many jobs were left, only counted by the first call
+    fn close(&self) -> ShutdownReport {
+        let executed = self.shared.close();
+
+        let mut timers = self.scheduler.timers.lock().unwrap();
+        let delayed = mem::take(&mut timers.jobs);
+        timers.closed = true;
+        drop(timers);
+        self.scheduler.timer_changed.notify_all();
+        // Dropped outside the lock, as dropping them can run user code
+        let discarded = delayed.len();
+        drop(delayed);
+        if let Some(thread) = self.scheduler_thread.lock().unwrap().take() {
+            // The scheduler only panics if the queue has been poisoned
+            let _ = thread.join();
+        }
+
+        *self.monitor.stopped.lock().unwrap() = true;
+        self.monitor.stop.notify_all();
+        if let Some(thread) = self.monitor_thread.lock().unwrap().take() {
+            // The last handle of the pool may be dropped by the monitor
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+        if let Some(thread) = self.supervisor_thread.lock().unwrap().take() {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+        ShutdownReport {
+            executed,
+            discarded,
+        }
+    }
+
+    /// Replaces the workers whose threads have exited with new ones
+    /// under the same IDs, returns how many were respawned
+    ///
+    /// The locks poisoned by the dead workers are recovered first, and
+    /// the jobs left in the queues are picked up by the new workers.
+    /// A worker failing to respawn is logged and retried later
+    fn respawn_dead_workers(&self) -> usize {
+        if self.shared.is_closed() {
+            return 0;
+        }
+        let mut workers = self.workers.lock().unwrap();
+        let dead: Vec<usize> = workers
+            .iter()
+            .filter(|worker| !worker.is_running())
+            .map(|worker| worker.id)
+            .collect();
+        if dead.is_empty() {
+            return 0;
+        }
+        // A poisoning worker only exits once it has released the locks
+        self.shared.clear_poison();
+        // Forgotten before respawning, as `set_num_threads` waits
+        // for the workers it terminates to show up here
+        self.shared
+            .exited
+            .lock()
+            .unwrap()
+            .retain(|id| !dead.contains(id));
+
+        let mut respawned = 0;
+        for worker in workers.iter_mut().filter(|worker| !worker.is_running()) {
+            worker.try_join();
+            let thread_builder = self.builder.thread_builder(worker.id);
+            match Worker::new(worker.id, self.shared.clone(), thread_builder) {
+                Ok(new) => {
+                    log::warn!(
+                        "{} has been respawned",
+                        self.shared.describe_worker(worker.id)
+                    );
+                    *worker = new;
+                    respawned += 1;
+                }
+                Err(err) => log::warn!(
+                    "{} could not be respawned: {}",
+                    self.shared.describe_worker(worker.id),
+                    err
+                ),
+            }
+        }
+        respawned
+    }
+
+    /// Closes the queue and joins every worker, returns the IDs of the ones
+    /// that had panicked
+    fn shutdown(&self) -> Vec<usize> {
+        self.close();
+        let mut workers = self.workers.lock().unwrap_or_else(PoisonError::into_inner);
+        // Every worker is joined, even after one of them has panicked
+        let mut panicked = Vec::new();
+        for worker in workers.iter_mut() {
+            if !worker.try_join() {
+                panicked.push(worker.id);
+            }
+        }
+        panicked
+    }
+
+    /// Recovers the locks from poisoning, closes the queue and joins every
+    /// worker, ignoring the ones that panicked
+    fn tear_down(&self) {
+        self.shared.clear_poison();
+        self.scheduler.timers.clear_poison();
+        self.scheduler_thread.clear_poison();
+        self.monitor.stopped.clear_poison();
+        self.monitor_thread.clear_poison();
+        self.supervisor_thread.clear_poison();
+        self.close();
+
+        let mut workers = self.workers.lock().unwrap_or_else(PoisonError::into_inner);
+        for worker in workers.iter_mut() {
+            if let Some(thread) = worker.thread.take() {
+                if thread.thread().id() != thread::current().id() {
+                    let _ = thread.join();
+                }
+            }
+        }
+        drop(workers);
+        self.workers.clear_poison();
+    }
+}
+
+impl ThreadPool {
+    /// The priority of the jobs sent through `execute`
+    pub const DEFAULT_PRIORITY: u8 = 128;
+
+    /// The number of panic messages kept for `take_panics`
+    pub const PANIC_LOG_CAPACITY: usize = 64;
+
+    /// The length below which `par_sort` sorts on the calling thread
+    pub const PAR_SORT_THRESHOLD: usize = 4096;
+
+    /// Whether the workers survive their panicking jobs, which requires
+    /// the `panic = "unwind"` strategy. With `panic = "abort"`, the first
+    /// panicking job aborts the process
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     assert_eq!(ThreadPool::CATCHES_PANICS, cfg!(panic = "unwind"));
+    ///
+    ///     if ThreadPool::CATCHES_PANICS {
+    ///         let pool = ThreadPool::build(1).unwrap();
+    ///         pool.execute(|| panic!("Error")).unwrap();
+    ///         pool.join();
+    ///         assert_eq!(pool.panic_count(), 1);
+    ///     }
+    /// }
+    /// ```
+    pub const CATCHES_PANICS: bool = cfg!(panic = "unwind");
+
+    /// Returns a `Result<ThreadPool, PoolError>`
+    ///
+    /// This is a shorthand for `ThreadPoolBuilder::new().num_threads(thread_count).build()`,
+    /// see `ThreadPoolBuilder` for more configuration options
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_count` - The number of threads in the pool,
+    ///   if `thread_count == 0`, returns an `Err`
+    ///
+    /// # Examples
+    ///
+    /// This code panics
+    ///
+    /// ```should_panic
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     ThreadPool::build(0).unwrap();
+    /// }
+    /// ```
+    ///
+    /// `thread_count` is unsigned, so 0 is the only invalid value and
+    /// a negative one would not compile. `build_nonzero` rules out 0 as well
+    ///
+    /// ```compile_fail
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     ThreadPool::build(-1);
+    /// }
+    /// ```
+    ///
+    #[must_use = "dropping the built pool shuts it down right away"]
+    pub fn build(thread_count: usize) -> Result<ThreadPool, PoolError> {
+        ThreadPoolBuilder::new().num_threads(thread_count).build()
+    }
+
+    /// Returns a `Result<Arc<ThreadPool>, PoolError>`, for code that
+    /// shares the pool behind an `Arc`
+    ///
+    /// A `ThreadPool` is already a cheaply cloneable handle, so cloning it
+    /// directly works as well
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_count` - The number of threads in the pool,
+    ///   if `thread_count == 0`, returns an `Err`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc};
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build_shared(2).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let producers: Vec<_> = (0..2)
+    ///         .map(|i| {
+    ///             let pool = Arc::clone(&pool);
+    ///             let sender = sender.clone();
+    ///             thread::spawn(move || pool.execute(move || sender.send(i).unwrap()).unwrap())
+    ///         })
+    ///         .collect();
+    ///     for producer in producers {
+    ///         producer.join().unwrap();
+    ///     }
+    ///     drop(sender);
+    ///
+    ///     let mut results: Vec<i32> = receiver.iter().collect();
+    ///     results.sort();
+    ///     assert_eq!(results, [0, 1]);
+    /// }
+    /// ```
+    #[must_use = "dropping the built pool shuts it down right away"]
+    pub fn build_shared(thread_count: usize) -> Result<Arc<ThreadPool>, PoolError> {
+        ThreadPool::build(thread_count).map(Arc::new)
+    }
+
+    /// Returns a `Result<ThreadPool, PoolError>` with a thread count
+    /// that can not be 0
+    ///
+    /// Only fails if a worker thread can not be spawned
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_count` - The number of threads in the pool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::num::NonZeroUsize;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build_nonzero(NonZeroUsize::new(3).unwrap()).unwrap();
+    ///     assert_eq!(pool.thread_count(), 3);
+    /// }
+    /// ```
+    #[must_use = "dropping the built pool shuts it down right away"]
+    pub fn build_nonzero(thread_count: NonZeroUsize) -> Result<ThreadPool, PoolError> {
+        ThreadPool::build(thread_count.get())
+    }
+
+    /// Returns a `Result<ThreadPool, PoolError>`, falling back to one
+    /// thread per available CPU if `thread_count == 0`
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_count` - The number of threads in the pool, or 0 for
+    ///   the thread count of `ThreadPool::default`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build_or_default(2).unwrap();
+    ///     assert_eq!(pool.thread_count(), 2);
+    ///
+    ///     let cpus = thread::available_parallelism().map_or(1, |count| count.get());
+    ///     let pool = ThreadPool::build_or_default(0).unwrap();
+    ///     assert_eq!(pool.thread_count(), cpus);
+    /// }
+    /// ```
+    #[must_use = "dropping the built pool shuts it down right away"]
+    pub fn build_or_default(thread_count: usize) -> Result<ThreadPool, PoolError> {
+        match NonZeroUsize::new(thread_count) {
+            Some(thread_count) => ThreadPool::build_nonzero(thread_count),
+            None => ThreadPool::build_nonzero(default_thread_count()),
+        }
+    }
+
+    /// Returns a `Result<ThreadPool, PoolError>` with the thread count
+    /// read from the environment variable `name`
+    ///
+    /// Like `build_or_default`, falls back to one thread per available CPU
+    /// if the variable is unset or holds 0. If it holds anything else than
+    /// a number, surrounding whitespace aside, returns an `Err`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::env;
+    /// use std::thread;
+    /// use threadpool::{PoolError, ThreadPool};
+    /// fn main() {
+    ///     let cpus = thread::available_parallelism().map_or(1, |count| count.get());
+    ///
+    ///     env::remove_var("POOL_THREADS");
+    ///     assert_eq!(ThreadPool::from_env("POOL_THREADS").unwrap().thread_count(), cpus);
+    ///
+    ///     env::set_var("POOL_THREADS", "3");
+    ///     assert_eq!(ThreadPool::from_env("POOL_THREADS").unwrap().thread_count(), 3);
+    ///
+    ///     env::set_var("POOL_THREADS", "0");
+    ///     assert_eq!(ThreadPool::from_env("POOL_THREADS").unwrap().thread_count(), cpus);
+    ///
+    ///     env::set_var("POOL_THREADS", "abc");
+    ///     match ThreadPool::from_env("POOL_THREADS") {
+    ///         Err(PoolError::InvalidEnvVar { name, value }) => {
+    ///             assert_eq!(name, "POOL_THREADS");
+    ///             assert_eq!(value, "abc");
+    ///         }
+    ///         _ => panic!("A garbage thread count should be rejected"),
+    ///     }
+    /// }
+    /// ```
+    #[must_use = "dropping the built pool shuts it down right away"]
+    pub fn from_env(name: &str) -> Result<ThreadPool, PoolError> {
+        let thread_count = match std::env::var_os(name) {
+            None => 0,
+            Some(value) => {
+                let value = value.to_string_lossy();
+                value.trim().parse().map_err(|_| PoolError::InvalidEnvVar {
+                    name: name.to_string(),
+                    value: value.into_owned(),
+                })?
+            }
+        };
+        ThreadPool::build_or_default(thread_count)
+    }
+
+    /// Returns the process-wide thread pool, built on first use like
+    /// `ThreadPool::default`, with one thread per available CPU
+    ///
+    /// The global pool lives in a static, so it is never dropped: its
+    /// workers run until the process exits, and the jobs still queued
+    /// at that point are not run. Call `join` before returning from `main`
+    /// to wait for them
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let result = ThreadPool::global().submit(|| 6 * 7);
+    ///     assert_eq!(result.recv().unwrap(), 42);
+    ///     assert!(std::ptr::eq(ThreadPool::global(), ThreadPool::global()));
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// The global pool is shared by the whole process, so calling
+    /// `shutdown` or `set_num_threads` on it affects every other user
+    ///
+    /// # Panics
+    ///
+    /// If not even a single worker thread can be spawned, this method panics
+    pub fn global() -> &'static ThreadPool {
+        static GLOBAL: OnceLock<ThreadPool> = OnceLock::new();
+        GLOBAL.get_or_init(ThreadPool::default)
+    }
+
+    /// Send a job to the thread pool to execute it
+    ///
+    /// # Arguments
+    ///
+    /// * `&self` - The pool only needs shared access, so it can be
+    ///   shared between producer threads (e.g. behind an `Arc`)
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// Several threads submitting jobs to the same pool
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc};
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = Arc::new(ThreadPool::build(4).unwrap());
+    ///     let (sender, receiver) = mpsc::channel();
+    ///
+    ///     let producers: Vec<_> = (0..4)
+    ///         .map(|i| {
+    ///             let pool = Arc::clone(&pool);
+    ///             let sender = sender.clone();
+    ///             thread::spawn(move || {
+    ///                 for j in 0..10 {
+    ///                     let sender = sender.clone();
+    ///                     pool.execute(move || sender.send(i * 10 + j).unwrap()).unwrap();
+    ///                 }
+    ///             })
+    ///         })
+    ///         .collect();
+    ///     for producer in producers {
+    ///         producer.join().unwrap();
+    ///     }
+    ///     drop(sender);
+    ///
+    ///     let mut results: Vec<i32> = receiver.iter().collect();
+    ///     results.sort();
+    ///     assert_eq!(results, (0..40).collect::<Vec<_>>());
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// If the callable panics, the panic is caught by the worker,
+    /// which then moves on to the next job. The panic message is
+    /// still printed by the panic hook
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     pool.execute(move || sender.send("Still alive").unwrap()).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), "Still alive");
+    /// }
+    /// ```
+    ///
+    /// The callable does not have to be `UnwindSafe`. The data it mutates
+    /// before panicking may be left half updated, which a `Mutex` reports
+    /// by being poisoned
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let data = Arc::new(Mutex::new(vec![1]));
+    ///     let job_data = Arc::clone(&data);
+    ///     pool.execute(move || {
+    ///         let mut data = job_data.lock().unwrap();
+    ///         data.push(2);
+    ///         panic!("Error");
+    ///     }).unwrap();
+    ///     pool.join();
+    ///
+    ///     assert!(pool.is_healthy());
+    ///     assert!(data.is_poisoned());
+    ///     assert_eq!(*data.lock().unwrap_or_else(|err| err.into_inner()), [1, 2]);
+    /// }
+    /// ```
+    ///
+    /// If the pool has been shut down, the job is dropped without being run
+    /// and `Err(PoolError::Shutdown)` is returned
+    ///
+    /// If every worker has died, e.g. from a job panicking in
+    /// `PanicMode::Abort`, the job would never run, so it is dropped as well
+    /// and `Err(PoolError::Dead)` is returned. Use `try_execute` to get
+    /// the job back instead
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{PanicMode, PoolError, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.workers().any(|worker| !worker.is_finished) {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///     assert!(matches!(pool.execute(|| {}), Err(PoolError::Dead)));
+    ///
+    ///     let pool = ThreadPoolBuilder::new().build().unwrap();
+    ///     pool.shutdown();
+    ///     assert!(matches!(pool.execute(|| {}), Err(PoolError::Shutdown)));
+    /// }
+    /// ```
+    ///
+    /// If the queue is bounded and full, this method blocks until a worker
+    /// frees a slot. Blocking a job could deadlock the pool, so when called
+    /// from within a job of the same pool, the job is queued regardless
+    pub fn execute<F>(&self, job: F) -> Result<(), PoolError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner
+            .shared
+            .try_push_job(Self::DEFAULT_PRIORITY, in_submission_span(Box::new(job)))
+    }
+
+    /// Send an already boxed job to the thread pool, without boxing
+    /// it again like `execute` would
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - The boxed callable
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::{Job, ThreadPool};
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let job: Job = Box::new(move || sender.send("Boxed").unwrap());
+    ///     pool.execute_job(job);
+    ///     assert_eq!(receiver.recv().unwrap(), "Boxed");
+    /// }
+    /// ```
+    pub fn execute_job(&self, job: Job) {
+        self.inner
+            .shared
+            .push_job(Self::DEFAULT_PRIORITY, in_submission_span(job));
+    }
+
+    /// Send a job to the thread pool without any observability,
+    /// e.g. for fire-and-forget logging
+    ///
+    /// The worker runs the job without timing or counting it: it is left
+    /// out of `active_count`, `completed_count`, `utilization` and the
+    /// tracing span of the submitter. A panic of the job is swallowed,
+    /// neither counted, logged nor handed to the panic handler, and does
+    /// not abort the pool in `PanicMode::Abort`. `join` still waits for
+    /// the job. A bounded or custom queue receives it like any other job
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let runs = Arc::new(AtomicUsize::new(0));
+    ///     for _ in 0..10 {
+    ///         let runs = Arc::clone(&runs);
+    ///         pool.execute_detached(move || {
+    ///             runs.fetch_add(1, Ordering::Relaxed);
+    ///         });
+    ///     }
+    ///     pool.execute_detached(|| panic!("Forgotten"));
+    ///     pool.join();
+    ///
+    ///     assert_eq!(runs.load(Ordering::Relaxed), 10);
+    ///     assert_eq!(pool.completed_count(), 0);
+    ///     assert_eq!(pool.panic_count(), 0);
+    /// }
+    /// ```
+    pub fn execute_detached<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner.shared.push_detached(Box::new(job));
+    }
+
+    /// Send a job to the thread pool, an alias of `execute` for those used
+    /// to `std::thread::spawn` and `rayon::spawn`
+    ///
+    /// `execute` remains the canonical name, used by the rest of the API
+    /// and its documentation. Both behave the same and neither is deprecated
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for i in 0..4 {
+    ///         let sender = sender.clone();
+    ///         pool.spawn(move || sender.send(i).unwrap()).unwrap();
+    ///     }
+    ///     drop(sender);
+    ///
+    ///     let mut results: Vec<i32> = receiver.iter().collect();
+    ///     results.sort();
+    ///     assert_eq!(results, vec![0, 1, 2, 3]);
+    /// }
+    /// ```
+    pub fn spawn<F>(&self, job: F) -> Result<(), PoolError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute(job)
+    }
+
+    /// Send a job to the thread pool to execute it before the queued
+    /// jobs of lower priority
+    ///
+    /// Jobs of equal priority are executed in submission order.
+    /// `execute` uses `ThreadPool::DEFAULT_PRIORITY`
+    ///
+    /// The order only holds within the queue of each worker, see
+    /// the scheduling section of `ThreadPool`, so it is only strict
+    /// for a pool built with a single thread
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The priority of the job, higher runs first
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc, Barrier};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///
+    ///     // Block the only worker while the jobs are queued
+    ///     let barrier = Arc::new(Barrier::new(2));
+    ///     let barrier_clone = Arc::clone(&barrier);
+    ///     pool.execute_with_priority(u8::MAX, move || {
+    ///         barrier_clone.wait();
+    ///     });
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for (priority, name) in [(1, "low 1"), (1, "low 2"), (200, "high 1"), (200, "high 2")] {
+    ///         let sender = sender.clone();
+    ///         pool.execute_with_priority(priority, move || sender.send(name).unwrap());
+    ///     }
+    ///     barrier.wait();
+    ///     drop(sender);
+    ///
+    ///     let order: Vec<_> = receiver.iter().collect();
+    ///     assert_eq!(order, ["high 1", "high 2", "low 1", "low 2"]);
+    /// }
+    /// ```
+    pub fn execute_with_priority<F>(&self, priority: u8, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner
+            .shared
+            .push_job(priority, in_submission_span(Box::new(job)));
+    }
+
+    /// Send a job to the thread pool, to run before the jobs of later
+    /// deadlines, in earliest-deadline-first order
+    ///
+    /// The job has `ThreadPool::DEFAULT_PRIORITY`, and runs before the
+    /// jobs of equal priority with a later deadline or without any, such
+    /// as the ones sent through `execute`. Jobs of equal deadline are
+    /// executed in submission order. The deadline is only used to order
+    /// the jobs: a job whose deadline has passed still runs
+    ///
+    /// The order only holds within the queue of each worker, see
+    /// the scheduling section of `ThreadPool`, so it is only strict
+    /// for a pool built with a single thread. A custom job queue
+    /// decides the order itself
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - When the job should have run by
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::time::{Duration, Instant};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///
+    ///     // Block the only worker while the jobs are queued
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///
+    ///     let now = Instant::now();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let sender_clone = sender.clone();
+    ///     pool.execute(move || sender_clone.send("no deadline").unwrap()).unwrap();
+    ///     for (millis, name) in [(300, "third"), (100, "first"), (200, "second 1"), (200, "second 2")] {
+    ///         let sender = sender.clone();
+    ///         let deadline = now + Duration::from_millis(millis);
+    ///         pool.execute_by_deadline(deadline, move || sender.send(name).unwrap());
+    ///     }
+    ///     release.send(()).unwrap();
+    ///     drop(sender);
+    ///
+    ///     let order: Vec<_> = receiver.iter().collect();
+    ///     assert_eq!(order, ["first", "second 1", "second 2", "third", "no deadline"]);
+    /// }
+    /// ```
+    pub fn execute_by_deadline<F>(&self, deadline: Instant, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner.shared.push_job_by(
+            Self::DEFAULT_PRIORITY,
+            Some(deadline),
+            in_submission_span(Box::new(job)),
+        );
+    }
+
+    /// Send a job to the thread pool, which receives the ID of the worker
+    /// running it, e.g. to pick a per-worker log file
+    ///
+    /// The IDs are the ones listed by `workers`, so they stay below
+    /// the thread count unless the pool has been resized. A job sent to
+    /// a pool sharing its custom queue with other pools may run on one
+    /// of their workers, and receives the ID of that worker
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce(usize) + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for _ in 0..100 {
+    ///         let sender = sender.clone();
+    ///         pool.execute_with_worker_id(move |id| {
+    ///             sender.send((id, thread::current().id())).unwrap();
+    ///         });
+    ///     }
+    ///     drop(sender);
+    ///
+    ///     // Each ID is reported by a single thread
+    ///     let mut threads = HashMap::new();
+    ///     for (id, thread) in receiver {
+    ///         assert!(id < pool.thread_count());
+    ///         assert_eq!(*threads.entry(id).or_insert(thread), thread);
+    ///     }
+    /// }
+    /// ```
+    pub fn execute_with_worker_id<F>(&self, job: F)
+    where
+        F: FnOnce(usize) + Send + 'static,
+    {
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || job(current_worker_id()));
+    }
+
+    /// Send a job to the thread pool with a label, listed by `active_labels`
+    /// while the job runs, e.g. to tell what a busy pool is doing
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - The label of the job, which does not have to be unique
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Barrier};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let started = Arc::new(Barrier::new(4));
+    ///     let finish = Arc::new(Barrier::new(4));
+    ///     for label in ["compaction", "upload", "upload"] {
+    ///         let started = Arc::clone(&started);
+    ///         let finish = Arc::clone(&finish);
+    ///         pool.execute_labeled(label, move || {
+    ///             started.wait();
+    ///             finish.wait();
+    ///         });
+    ///     }
+    ///
+    ///     started.wait();
+    ///     let mut labels = pool.active_labels();
+    ///     labels.sort();
+    ///     assert_eq!(labels, ["compaction", "upload", "upload"]);
+    ///     assert_eq!(pool.metrics().active_labels.len(), 3);
+    ///
+    ///     finish.wait();
+    ///     pool.join();
+    ///     assert!(pool.active_labels().is_empty());
+    /// }
+    /// ```
+    pub fn execute_labeled<F>(&self, label: &'static str, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let shared = Arc::clone(&self.inner.shared);
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            let _label = LabelGuard::new(&shared, label);
+            job();
+        });
+    }
+
+    /// Send a job to the thread pool to execute it on the worker with
+    /// ID `worker_id`, e.g. so that the jobs touching a shard of data
+    /// always run on the same thread
+    ///
+    /// The jobs sent to a worker run in submission order, before the jobs
+    /// of its local queue. They are not stolen by the other workers, do not
+    /// count towards the queue capacity and are run even if the worker is
+    /// asked to shut down by `set_num_threads`. The IDs of the workers are
+    /// listed by `workers`
+    ///
+    /// Returns `false`, dropping the job without running it, if no running
+    /// worker has ID `worker_id` or the pool has been shut down
+    ///
+    /// # Arguments
+    ///
+    /// * `worker_id` - The ID of the worker to run the job on
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for _ in 0..20 {
+    ///         let sender = sender.clone();
+    ///         assert!(pool.execute_on(0, move || sender.send(thread::current().id()).unwrap()));
+    ///     }
+    ///     drop(sender);
+    ///
+    ///     let threads: HashSet<_> = receiver.iter().collect();
+    ///     assert_eq!(threads.len(), 1);
+    ///
+    ///     assert!(!pool.execute_on(4, || {}));
+    /// }
+    /// ```
+    pub fn execute_on<F>(&self, worker_id: usize, job: F) -> bool
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // Holding the lock keeps `set_num_threads` from stopping
+        // the worker before it has picked the job up
+        let workers = self.inner.workers.lock().unwrap();
+        let is_running = workers
+            .iter()
+            .any(|worker| worker.id == worker_id && worker.is_running());
+        is_running
+            && self
+                .inner
+                .shared
+                .push_pinned(worker_id, in_submission_span(Box::new(job)))
+    }
+
+    /// Returns a `SequentialGroup`, whose jobs run one at a time in
+    /// submission order, concurrently with the other jobs of the pool
+    ///
+    /// The groups are spread over the workers in turn, each group sending
+    /// its jobs to a single worker as `execute_on` does
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let first = pool.sequential_group();
+    ///     let second = pool.sequential_group();
+    ///
+    ///     let first_order = Arc::new(Mutex::new(Vec::new()));
+    ///     let second_order = Arc::new(Mutex::new(Vec::new()));
+    ///     for i in 0..100 {
+    ///         let first_order = Arc::clone(&first_order);
+    ///         first.execute(move || first_order.lock().unwrap().push(i));
+    ///         let second_order = Arc::clone(&second_order);
+    ///         second.execute(move || second_order.lock().unwrap().push(i));
+    ///         // Contending jobs, free to run in any order
+    ///         pool.execute(|| std::thread::yield_now()).unwrap();
+    ///     }
+    ///     pool.join();
+    ///
+    ///     let expected: Vec<i32> = (0..100).collect();
+    ///     assert_eq!(*first_order.lock().unwrap(), expected);
+    ///     assert_eq!(*second_order.lock().unwrap(), expected);
+    /// }
+    /// ```
+    pub fn sequential_group(&self) -> SequentialGroup {
+        let workers = self.inner.workers.lock().unwrap();
+        let worker_id = SequentialGroup::pick_worker(&self.inner, &workers).unwrap_or(0);
+        drop(workers);
+        SequentialGroup {
+            pool: self.clone(),
+            worker_id: Mutex::new(worker_id),
+        }
+    }
+
+    /// Send several jobs to the thread pool, to run one at a time in
+    /// the order they are yielded, through a new `SequentialGroup`
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - The callables implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let order = Arc::new(Mutex::new(Vec::new()));
+    ///     pool.execute_batch_ordered((0..50).map(|i| {
+    ///         let order = Arc::clone(&order);
+    ///         move || order.lock().unwrap().push(i)
+    ///     }));
+    ///     pool.join();
+    ///     assert_eq!(*order.lock().unwrap(), (0..50).collect::<Vec<_>>());
+    /// }
+    /// ```
+    pub fn execute_batch_ordered<I, F>(&self, jobs: I)
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce() + Send + 'static,
+    {
+        let group = self.sequential_group();
+        for job in jobs {
+            group.execute(job);
+        }
+    }
+
+    /// Send several jobs to the thread pool at once
+    ///
+    /// The jobs are spread over the queues of the workers, each queue being
+    /// locked once, which is cheaper than calling `execute` for each of them
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - An iterator of callables implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let counter = Arc::new(AtomicUsize::new(0));
+    ///     let jobs: Vec<_> = (0..1000)
+    ///         .map(|_| {
+    ///             let counter = Arc::clone(&counter);
+    ///             move || {
+    ///                 counter.fetch_add(1, Ordering::SeqCst);
+    ///             }
+    ///         })
+    ///         .collect();
+    ///     pool.execute_all(jobs);
+    ///     pool.join();
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 1000);
+    /// }
+    /// ```
+    pub fn execute_all<I, F>(&self, jobs: I)
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce() + Send + 'static,
+    {
+        // The jobs are boxed before taking the lock, so that the iterator
+        // can not run user code while the queue is locked
+        let jobs: Vec<Job> = jobs
+            .into_iter()
+            .map(|job| in_submission_span(Box::new(job)))
+            .collect();
+        self.inner.shared.push_jobs(Self::DEFAULT_PRIORITY, jobs);
+    }
+
+    /// Send a job mutating the executing worker's local value of type `L`
+    ///
+    /// See `ThreadPoolBuilder::with_worker_local` for setting up the value
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce(&mut L) + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .with_worker_local(|_| 0_u64)
+    ///         .build()
+    ///         .unwrap();
+    ///     for _ in 0..10 {
+    ///         pool.execute_with_local(|count: &mut u64| *count += 1);
+    ///     }
+    ///     pool.join();
+    ///
+    ///     let (sender, receiver) = std::sync::mpsc::channel();
+    ///     pool.execute_with_local(move |count: &mut u64| sender.send(*count).unwrap());
+    ///     assert_eq!(receiver.recv().unwrap(), 10);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If no worker-local value of type `L` has been set up, this method panics
+    ///
+    /// ```should_panic
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     pool.execute_with_local(|_: &mut String| {});
+    /// }
+    /// ```
+    pub fn execute_with_local<L, F>(&self, job: F)
+    where
+        L: 'static,
+        F: FnOnce(&mut L) + Send + 'static,
+    {
+        let type_id = TypeId::of::<L>();
+        assert!(
+            self.inner
+                .shared
+                .worker_locals
+                .iter()
+                .any(|(id, _)| *id == type_id),
+            "No worker-local value of type {} has been set up",
+            std::any::type_name::<L>()
+        );
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            WORKER_LOCALS.with(|locals| {
+                let mut locals = locals.borrow_mut();
+                let local = locals
+                    .get_mut(&type_id)
+                    .and_then(|local| local.downcast_mut::<L>())
+                    .expect("The worker-local value is set up as the worker starts");
+                job(local);
+            })
+        });
+    }
+
+    /// Send a job receiving a `PoolHandle` to the thread pool, which
+    /// the job can use to send more jobs, e.g. to reschedule itself
+    ///
+    /// Jobs sent from within a job never block, even if the queue is
+    /// bounded and full, since all the workers could be waiting for
+    /// each other. Once the pool has been shut down, they are dropped
+    /// without being run
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce(&PoolHandle) + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::{PoolHandle, ThreadPool};
+    ///
+    /// const RESCHEDULES: usize = 5;
+    ///
+    /// fn poll(handle: &PoolHandle, runs: Arc<AtomicUsize>) {
+    ///     if runs.fetch_add(1, Ordering::SeqCst) < RESCHEDULES {
+    ///         handle.execute_with_handle(move |handle| poll(handle, runs));
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let runs = Arc::new(AtomicUsize::new(0));
+    ///     let job_runs = Arc::clone(&runs);
+    ///     pool.execute_with_handle(move |handle| poll(handle, job_runs));
+    ///
+    ///     // A rescheduled job is queued before its predecessor finishes
+    ///     pool.join();
+    ///     assert_eq!(runs.load(Ordering::SeqCst), RESCHEDULES + 1);
+    /// }
+    /// ```
+    pub fn execute_with_handle<F>(&self, job: F)
+    where
+        F: FnOnce(&PoolHandle) + Send + 'static,
+    {
+        PoolHandle {
+            shared: Arc::clone(&self.inner.shared),
+        }
+        .execute_with_handle(job);
+    }
+
+    /// Send a job to the thread pool that runs repeatedly until it returns
+    /// `ControlFlow::Break`
+    ///
+    /// Each iteration runs as a job of its own, requeued once the previous
+    /// one returns `ControlFlow::Continue`. This keeps a long-running loop
+    /// from monopolizing a worker, as the jobs queued meanwhile get to run
+    /// between its iterations, at the cost of a queue round trip per
+    /// iteration, which may also move the loop to another worker. A loop
+    /// needing a thread to itself can rather be run by `execute` as a
+    /// plain `loop`
+    ///
+    /// Like jobs sent from within a job, the requeued iterations never block
+    /// on a full bounded queue. Once the pool has been shut down, or if an
+    /// iteration panics, the loop stops
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnMut() -> ControlFlow<()> + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let runs = Arc::new(AtomicUsize::new(0));
+    ///     let job_runs = Arc::clone(&runs);
+    ///     pool.execute_loop(move || {
+    ///         if job_runs.fetch_add(1, Ordering::SeqCst) + 1 == 5 {
+    ///             ControlFlow::Break(())
+    ///         } else {
+    ///             ControlFlow::Continue(())
+    ///         }
+    ///     });
+    ///
+    ///     // The next iteration is queued before the previous one finishes
+    ///     pool.join();
+    ///     assert_eq!(runs.load(Ordering::SeqCst), 5);
+    /// }
+    /// ```
+    pub fn execute_loop<F>(&self, job: F)
+    where
+        F: FnMut() -> ControlFlow<()> + Send + 'static,
+    {
+        self.execute_with_handle(move |handle| run_loop(handle, job));
+    }
+
+    /// Send a job receiving the pool's shutdown token to the thread pool
+    ///
+    /// The token is cancelled once the pool is shut down, by `shutdown`,
+    /// `shutdown_timeout` or dropping its last handle, so that long-running
+    /// jobs polling it can stop early instead of delaying the shutdown.
+    /// The jobs still queued at that point are run with the token
+    /// already cancelled
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce(&CancellationToken) + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::{Duration, Instant};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     pool.execute_with_shutdown(move |token| {
+    ///         started_sender.send(()).unwrap();
+    ///         while !token.is_cancelled() {
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///     });
+    ///
+    ///     started.recv().unwrap();
+    ///     let start = Instant::now();
+    ///     pool.shutdown();
+    ///     assert!(start.elapsed() < Duration::from_secs(5));
+    /// }
+    /// ```
+    pub fn execute_with_shutdown<F>(&self, job: F)
+    where
+        F: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        let token = self.inner.shared.shutdown_token.clone();
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || job(&token));
+    }
+
+    /// Send a job to the thread pool, running it again right away each
+    /// time it panics, up to `max_attempts` runs in total
+    ///
+    /// The panics of the attempts that are retried are caught silently.
+    /// Only the panic of the last attempt goes through the pool's panic
+    /// handling, being counted by `panic_count` and recorded by
+    /// `take_panics`
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - The maximum number of runs, at least 1
+    ///
+    /// * `job` - A callable implementing `Fn() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let attempts = Arc::new(AtomicUsize::new(0));
+    ///     let job_attempts = Arc::clone(&attempts);
+    ///     pool.execute_with_retry(5, move || {
+    ///         if job_attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+    ///             panic!("Flaky");
+    ///         }
+    ///     });
+    ///     pool.join();
+    ///     assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    ///     assert_eq!(pool.panic_count(), 0);
+    ///     assert!(pool.is_healthy());
+    ///
+    ///     pool.execute_with_retry(3, || panic!("Broken"));
+    ///     pool.join();
+    ///     assert_eq!(pool.panic_count(), 1);
+    ///     assert_eq!(pool.take_panics(), ["Broken"]);
+    /// }
+    /// ```
+    pub fn execute_with_retry<F>(&self, max_attempts: usize, job: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            for _ in 1..max_attempts {
+                if panic::catch_unwind(AssertUnwindSafe(&job)).is_ok() {
+                    return;
+                }
+            }
+            // The last attempt panics like any other job
+            job();
+        });
+    }
+
+    /// Send a job to the thread pool without blocking
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// The rejected job is handed back, so that it can be retried
+    /// or run elsewhere
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::{JobRejected, ThreadPool};
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     assert!(pool.try_execute(|| {}).is_ok());
+    ///
+    ///     pool.shutdown();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     match pool.try_execute(move || sender.send("Run by the caller").unwrap()) {
+    ///         Err(JobRejected::Shutdown(job)) => job(),
+    ///         _ => panic!("A shut down pool should reject the job"),
+    ///     }
+    ///     assert_eq!(receiver.recv().unwrap(), "Run by the caller");
+    /// }
+    /// ```
+    ///
+    /// If the queue is bounded and full, returns `Err(JobRejected::Full)`,
+    /// if the pool has been shut down, `Err(JobRejected::Shutdown)`, and
+    /// if every worker has died, e.g. from a job panicking in
+    /// `PanicMode::Abort`, `Err(JobRejected::Dead)`
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{JobRejected, PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.workers().any(|worker| !worker.is_finished) {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///
+    ///     // The job is handed back rather than queued for no worker
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     match pool.try_execute(move || sender.send("Run by the caller").unwrap()) {
+    ///         Err(JobRejected::Dead(job)) => job(),
+    ///         _ => panic!("A dead pool should reject the job"),
+    ///     }
+    ///     assert_eq!(receiver.recv().unwrap(), "Run by the caller");
+    /// }
+    /// ```
+    pub fn try_execute<F>(&self, job: F) -> Result<(), JobRejected<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_blocking_timeout(Duration::ZERO, job)
+    }
+
+    /// Send a job to the thread pool, waiting at most `timeout` for
+    /// a free slot in its bounded queue
+    ///
+    /// Unlike `execute`, which blocks while the queue is full, the job is
+    /// handed back once the deadline has passed, so that a producer is
+    /// slowed down by a backlog without getting stuck on it. An unbounded
+    /// pool accepts every job right away until it is shut down
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait for the queue to have room
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::time::{Duration, Instant};
+    /// use threadpool::{JobRejected, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(1)
+    ///         .queue_capacity(1)
+    ///         .build()
+    ///         .unwrap();
+    ///
+    ///     // The worker is kept busy and the queue full
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///     pool.execute(|| {}).unwrap();
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let start = Instant::now();
+    ///     let job = move || sender.send("Run by the caller").unwrap();
+    ///     match pool.execute_blocking_timeout(Duration::from_millis(50), job) {
+    ///         Err(JobRejected::Full(job)) => job(),
+    ///         _ => panic!("A full queue should reject the job"),
+    ///     }
+    ///     assert!(start.elapsed() >= Duration::from_millis(50));
+    ///     assert_eq!(receiver.recv().unwrap(), "Run by the caller");
+    ///
+    ///     // A slot freed up before the deadline lets the job in
+    ///     release.send(()).unwrap();
+    ///     assert!(pool
+    ///         .execute_blocking_timeout(Duration::from_secs(10), || {})
+    ///         .is_ok());
+    ///     pool.join();
+    /// }
+    /// ```
+    ///
+    /// If the queue is still full once `timeout` has passed, returns
+    /// `Err(JobRejected::Full)`, if the pool has been shut down,
+    /// `Err(JobRejected::Shutdown)`, and if every worker has died,
+    /// `Err(JobRejected::Dead)`
+    pub fn execute_blocking_timeout<F>(
+        &self,
+        timeout: Duration,
+        job: F,
+    ) -> Result<(), JobRejected<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let shared = &self.inner.shared;
+        // Checked first, as the locks of a dead pool may be poisoned
+        if shared.is_dead() {
+            return Err(JobRejected::Dead(job));
+        }
+        // Fullness is only checked consistently under the control lock,
+        // which is released while waiting for a free slot
+        let control = (shared.capacity.is_some() || shared.job_queue.is_some()).then(|| {
+            let control = shared.control.lock().unwrap();
+            shared
+                .not_full
+                .wait_timeout_while(control, timeout, |_| {
+                    !shared.is_closed() && shared.is_full()
+                })
+                .unwrap()
+                .0
+        });
+        if shared.is_closed() {
+            return Err(JobRejected::Shutdown(job));
+        }
+        if shared.is_full() {
+            return Err(JobRejected::Full(job));
+        }
+        if let Some(queue) = &shared.job_queue {
+            shared.enqueue_custom(queue.as_ref(), in_submission_span(Box::new(job)));
+            drop(control);
+            shared.spawn_on_demand();
+            return Ok(());
+        }
+        let queue = shared.least_loaded_queue();
+        let mut jobs = queue.jobs.lock().unwrap();
+        if shared.is_closed() {
+            return Err(JobRejected::Shutdown(job));
+        }
+        shared.enqueue(
+            queue,
+            &mut jobs,
+            Self::DEFAULT_PRIORITY,
+            None,
+            vec![in_submission_span(Box::new(job))],
+            false,
+        );
+        drop(jobs);
+        match control {
+            Some(_control) => shared.job_available.notify_one(),
+            None => shared.wake_workers(1),
+        }
+        shared.spawn_on_demand();
+        Ok(())
+    }
+
+    /// Send a job to the thread pool unless its bounded queue is full,
+    /// e.g. to shed load instead of blocking the producer
+    ///
+    /// Returns `false`, dropping the job without running it, if the queue
+    /// is full or the pool has been shut down. An unbounded pool accepts
+    /// every job until it is shut down. Use `try_execute` to get
+    /// the rejected job back
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new().queue_capacity(2).build().unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///
+    ///     let counter = Arc::new(AtomicUsize::new(0));
+    ///     let accepted: Vec<bool> = (0..4)
+    ///         .map(|_| {
+    ///             let counter = Arc::clone(&counter);
+    ///             pool.execute_unless_full(move || {
+    ///                 counter.fetch_add(1, Ordering::SeqCst);
+    ///             })
+    ///         })
+    ///         .collect();
+    ///     assert_eq!(accepted, [true, true, false, false]);
+    ///
+    ///     release.send(()).unwrap();
+    ///     pool.join();
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 2);
+    /// }
+    /// ```
+    pub fn execute_unless_full<F>(&self, job: F) -> bool
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.try_execute(job).is_ok()
+    }
+
+    /// Send a cancellable job to the thread pool and returns a `JobHandle`
+    /// for cancelling it
+    ///
+    /// The job receives a `CancellationToken` it can poll, since
+    /// cancelling a running job does not interrupt it
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce(CancellationToken) + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// A job cancelled before it starts is never run
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || released.recv().unwrap()).unwrap();
+    ///
+    ///     let ran = Arc::new(AtomicBool::new(false));
+    ///     let job_ran = Arc::clone(&ran);
+    ///     let handle = pool.execute_cancellable(move |_| job_ran.store(true, Ordering::SeqCst));
+    ///     handle.cancel();
+    ///
+    ///     release.send(()).unwrap();
+    ///     pool.join();
+    ///     assert!(!ran.load(Ordering::SeqCst));
+    /// }
+    /// ```
+    ///
+    /// A running job sees the cancellation through its token
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let handle = pool.execute_cancellable(move |token| {
+    ///         started_sender.send(()).unwrap();
+    ///         while !token.is_cancelled() {
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///         sender.send("Cancelled").unwrap();
+    ///     });
+    ///
+    ///     started.recv().unwrap();
+    ///     handle.cancel();
+    ///     assert_eq!(receiver.recv().unwrap(), "Cancelled");
+    /// }
+    /// ```
+    pub fn execute_cancellable<F>(&self, job: F) -> JobHandle
+    where
+        F: FnOnce(CancellationToken) + Send + 'static,
+    {
+        let (job, handle) = JobHandle::wrap(&self.inner.shared, job);
+        self.inner.shared.push_job(Self::DEFAULT_PRIORITY, job);
+        handle
+    }
+
+    /// Send a job to the thread pool and returns a `JobHandle`, through
+    /// which jobs can be chained to run once it has finished
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc, Mutex};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let events = Arc::new(Mutex::new(Vec::new()));
+    ///     let (sender, receiver) = mpsc::channel();
+    ///
+    ///     let a_events = Arc::clone(&events);
+    ///     let b_events = Arc::clone(&events);
+    ///     pool.execute_tracked(move || {
+    ///         thread::sleep(Duration::from_millis(50));
+    ///         a_events.lock().unwrap().push("A");
+    ///     })
+    ///     .then(move || {
+    ///         b_events.lock().unwrap().push("B");
+    ///         sender.send(()).unwrap();
+    ///     });
+    ///
+    ///     receiver.recv().unwrap();
+    ///     assert_eq!(*events.lock().unwrap(), vec!["A", "B"]);
+    /// }
+    /// ```
+    pub fn execute_tracked<F>(&self, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_cancellable(move |_| job())
+    }
+
+    /// Send a job to the thread pool once every job of `deps` has finished,
+    /// and returns its `JobHandle`
+    ///
+    /// Each dependency counts down as it finishes, see `JobHandle::then`,
+    /// and the last one submits `finalizer`. Without dependencies,
+    /// `finalizer` is submitted right away
+    ///
+    /// # Arguments
+    ///
+    /// * `deps` - The handles of the jobs to wait for
+    ///
+    /// * `finalizer` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let finished = Arc::new(AtomicUsize::new(0));
+    ///     let deps: Vec<_> = (0..3)
+    ///         .map(|i| {
+    ///             let finished = Arc::clone(&finished);
+    ///             pool.execute_tracked(move || {
+    ///                 thread::sleep(Duration::from_millis(10 * i));
+    ///                 finished.fetch_add(1, Ordering::SeqCst);
+    ///             })
+    ///         })
+    ///         .collect();
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let job_finished = Arc::clone(&finished);
+    ///     pool.execute_after_all(deps, move || {
+    ///         sender.send(job_finished.load(Ordering::SeqCst)).unwrap();
+    ///     });
+    ///
+    ///     // The finalizer ran once, after the three dependencies
+    ///     assert_eq!(receiver.recv().unwrap(), 3);
+    ///     pool.join();
+    ///     assert!(receiver.try_recv().is_err());
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute_after_all(Vec::new(), move || sender.send("Right away").unwrap());
+    ///     assert_eq!(receiver.recv().unwrap(), "Right away");
+    /// }
+    /// ```
+    pub fn execute_after_all<F>(&self, deps: Vec<JobHandle>, finalizer: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let shared = &self.inner.shared;
+        let (finalizer, handle) = JobHandle::wrap(shared, move |_| finalizer());
+        if deps.is_empty() {
+            shared.push_job(Self::DEFAULT_PRIORITY, finalizer);
+            return handle;
+        }
+
+        let remaining = Arc::new(AtomicUsize::new(deps.len()));
+        let finalizer = Arc::new(Mutex::new(Some(finalizer)));
+        for dep in deps {
+            let remaining = Arc::clone(&remaining);
+            let finalizer = Arc::clone(&finalizer);
+            let shared = Arc::clone(shared);
+            dep.then(move || {
+                if remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    if let Some(finalizer) = finalizer.lock().unwrap().take() {
+                        shared.push_job(Self::DEFAULT_PRIORITY, finalizer);
+                    }
+                }
+            });
+        }
+        handle
+    }
+
+    /// Send a job to the thread pool and returns a `TimeoutHandle`
+    /// reporting whether it runs for longer than `timeout`
+    ///
+    /// A thread can not be killed, so the timeout is only observational:
+    /// a job running past it keeps running, but the caller can react to
+    /// `TimeoutHandle::timed_out`. The timeout starts once a worker picks
+    /// the job up
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The time the job is expected to run within
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let slow = pool.execute_timeout(Duration::from_millis(20), || {
+    ///         thread::sleep(Duration::from_millis(100));
+    ///     });
+    ///     let fast = pool.execute_timeout(Duration::from_secs(10), || {});
+    ///
+    ///     thread::sleep(Duration::from_millis(50));
+    ///     assert!(slow.timed_out());
+    ///     assert!(!slow.is_finished());
+    ///
+    ///     pool.join();
+    ///     assert!(slow.timed_out());
+    ///     assert!(!fast.timed_out());
+    ///     assert!(fast.is_finished());
+    /// }
+    /// ```
+    pub fn execute_timeout<F>(&self, timeout: Duration, job: F) -> TimeoutHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let timing = Arc::new(Mutex::new(Timing::default()));
+        let job_timing = Arc::clone(&timing);
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            job_timing.lock().unwrap().started = Some(Instant::now());
+            // Recording the end in a guard covers a panicking job too
+            let _finish = FinishTiming { timing: job_timing };
+            job();
+        });
+        TimeoutHandle { timeout, timing }
+    }
+
+    /// Send a job to the thread pool once `delay` has elapsed
+    ///
+    /// The job is held by a dedicated scheduler thread, spawned on the
+    /// first call, which submits it with the default priority when its
+    /// deadline passes. `join` does not wait for the jobs that are not
+    /// due yet, and shutting the pool down drops them without running them
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - The time to wait before submitting the job
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let ran = Arc::new(AtomicBool::new(false));
+    ///     let job_ran = Arc::clone(&ran);
+    ///     pool.execute_after(Duration::from_millis(100), move || {
+    ///         job_ran.store(true, Ordering::SeqCst);
+    ///     });
+    ///
+    ///     thread::sleep(Duration::from_millis(50));
+    ///     assert!(!ran.load(Ordering::SeqCst));
+    ///     thread::sleep(Duration::from_millis(150));
+    ///     assert!(ran.load(Ordering::SeqCst));
+    /// }
+    /// ```
+    ///
+    /// A job falling due while every worker is dead is dropped with a
+    /// warning, as `execute` would reject it, and the later jobs run once
+    /// the pool has been resized
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let lost = sender.clone();
+    ///     pool.execute_after(Duration::from_millis(50), move || {
+    ///         lost.send("Lost").unwrap();
+    ///     });
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.workers().any(|worker| !worker.is_finished) {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///     thread::sleep(Duration::from_millis(100));
+    ///
+    ///     pool.set_num_threads(2).unwrap();
+    ///     pool.execute_after(Duration::from_millis(10), move || {
+    ///         sender.send("Delayed").unwrap();
+    ///     });
+    ///     assert_eq!(receiver.recv().unwrap(), "Delayed");
+    ///     pool.join();
+    ///     assert!(receiver.try_recv().is_err());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the OS fails to spawn the scheduler thread, this method panics
+    pub fn execute_after<F>(&self, delay: Duration, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let scheduler = &self.inner.scheduler;
+        // The thread lock is taken first, so that a concurrent `close`
+        // joins the scheduler spawned here
+        let mut scheduler_thread = self.inner.scheduler_thread.lock().unwrap();
+        let mut timers = scheduler.timers.lock().unwrap();
+        if timers.closed {
+            return;
+        }
+        let seq = timers.next_seq;
+        timers.next_seq += 1;
+        timers.jobs.push(DelayedJob {
+            deadline: Instant::now() + delay,
+            seq,
+            job: in_submission_span(Box::new(job)),
+        });
+        drop(timers);
+        scheduler.timer_changed.notify_one();
+
+        // A scheduler killed by the locks poisoned in `PanicMode::Abort`
+        // is replaced, so that the delayed jobs run once the pool recovers
+        if let Some(thread) = scheduler_thread.take_if(|thread| thread.is_finished()) {
+            let _ = thread.join();
+        }
+        if scheduler_thread.is_none() {
+            let scheduler = Arc::clone(scheduler);
+            let shared = Arc::clone(&self.inner.shared);
+            let thread = thread::Builder::new()
+                .name(format!("{}-scheduler", self.inner.builder.thread_prefix()))
+                .spawn(move || scheduler.run(&shared))
+                .expect("Failed to spawn the scheduler thread");
+            *scheduler_thread = Some(thread);
+        }
+    }
+
+    /// Discards every job that has not been picked up by a worker yet,
+    /// returns the number of discarded jobs
+    ///
+    /// The jobs being executed are left to finish, and the pool keeps
+    /// accepting new jobs afterwards
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute(move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     }).unwrap();
+    ///     started.recv().unwrap();
+    ///
+    ///     let counter = Arc::new(AtomicUsize::new(0));
+    ///     for _ in 0..10 {
+    ///         let counter = Arc::clone(&counter);
+    ///         pool.execute(move || {
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///         }).unwrap();
+    ///     }
+    ///     assert_eq!(pool.clear_queue(), 10);
+    ///
+    ///     release.send(()).unwrap();
+    ///     pool.join();
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 0);
+    /// }
+    /// ```
+    pub fn clear_queue(&self) -> usize {
+        self.inner.shared.clear()
+    }
+
+    /// Stops the workers from picking up jobs until `resume` is called
+    ///
+    /// The running jobs finish, while the queued and newly sent jobs wait
+    /// in the queue, so `join` blocks until the pool is resumed. Shutting
+    /// the pool down still runs the queued jobs
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     pool.pause();
+    ///     assert!(pool.is_paused());
+    ///
+    ///     let counter = Arc::new(AtomicUsize::new(0));
+    ///     for _ in 0..10 {
+    ///         let counter = Arc::clone(&counter);
+    ///         pool.execute(move || {
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///         }).unwrap();
+    ///     }
+    ///     thread::sleep(Duration::from_millis(100));
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 0);
+    ///     assert_eq!(pool.queued_count(), 10);
+    ///
+    ///     pool.resume();
+    ///     pool.join();
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 10);
+    /// }
+    /// ```
+    pub fn pause(&self) {
+        let shared = &self.inner.shared;
+        let _control = shared.control.lock().unwrap();
+        shared.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Lets the workers of a paused pool pick up jobs again
+    pub fn resume(&self) {
+        let shared = &self.inner.shared;
+        let _control = shared.control.lock().unwrap();
+        shared.paused.store(false, Ordering::SeqCst);
+        shared.job_available.notify_all();
+    }
+
+    /// Returns whether the pool has been paused by `pause`
+    /// and not resumed since
+    pub fn is_paused(&self) -> bool {
+        self.inner.shared.paused.load(Ordering::SeqCst)
+    }
+
+    /// Stops accepting new jobs and waits for the workers to shut down
+    ///
+    /// The jobs queued before the call are still executed. Afterwards,
+    /// `execute` drops the jobs it receives without running them and
+    /// returns `Err(PoolError::Shutdown)`, and `set_num_threads` returns
+    /// an `Err`, for every handle of the pool
+    ///
+    /// Returns a `ShutdownReport` counting the jobs that were still queued,
+    /// and the delayed jobs dropped as they were not due yet. A pool that
+    /// had already been shut down reports no jobs
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::{PoolError, ThreadPool};
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let queued_sender = sender.clone();
+    ///     pool.execute(move || queued_sender.send("Queued").unwrap()).unwrap();
+    ///
+    ///     pool.shutdown();
+    ///     assert_eq!(receiver.recv().unwrap(), "Queued");
+    ///
+    ///     let rejected = pool.execute(move || sender.send("Rejected").unwrap());
+    ///     assert!(matches!(rejected, Err(PoolError::Shutdown)));
+    ///     assert!(receiver.recv().is_err());
+    ///     assert!(pool.set_num_threads(4).is_err());
+    /// }
+    /// ```
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Barrier, Arc};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{ShutdownReport, ThreadPool};
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let started = Arc::new(Barrier::new(3));
+    ///     for _ in 0..2 {
+    ///         let started = Arc::clone(&started);
+    ///         pool.execute(move || {
+    ///             started.wait();
+    ///             thread::sleep(Duration::from_millis(200));
+    ///         }).unwrap();
+    ///     }
+    ///     started.wait();
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for i in 0..5 {
+    ///         let sender = sender.clone();
+    ///         pool.execute(move || sender.send(i).unwrap()).unwrap();
+    ///     }
+    ///     pool.execute_after(Duration::from_secs(60), move || sender.send(5).unwrap());
+    ///
+    ///     let report = pool.shutdown();
+    ///     assert_eq!(report, ShutdownReport { executed: 5, discarded: 1 });
+    ///     assert_eq!(receiver.iter().count(), 5);
+    ///     assert_eq!(pool.shutdown(), ShutdownReport::default());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If one of the worker had panicked and thus, terminated prematurely,
+    /// this method panics
+    pub fn shutdown(&self) -> ShutdownReport {
+        let report = self.inner.close();
+        if !self.inner.shutdown().is_empty() {
+            panic_on_dead_workers();
+        }
+        report
+    }
+
+    /// Stops accepting new jobs like `shutdown`, but waits at most
+    /// `timeout` for the workers to shut down
+    ///
+    /// Returns `true` if every worker has shut down in time.
+    /// Otherwise, returns `false` and detaches the remaining workers,
+    /// which keep running their current jobs in the background, so that
+    /// dropping the pool afterwards does not wait for them
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum amount of time to wait for
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     pool.execute(|| thread::sleep(Duration::from_millis(10))).unwrap();
+    ///     assert!(pool.shutdown_timeout(Duration::from_secs(10)));
+    ///
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     pool.execute(|| thread::sleep(Duration::from_secs(1))).unwrap();
+    ///     assert!(!pool.shutdown_timeout(Duration::from_millis(50)));
+    /// }
+    /// ```
+    pub fn shutdown_timeout(&self, timeout: Duration) -> bool {
+        self.inner.close();
+
+        let mut workers = self.inner.workers.lock().unwrap();
+        let exited_guard = self.inner.shared.exited.lock().unwrap();
+        let (exited_guard, _) = self
+            .inner
+            .shared
+            .worker_exited
+            .wait_timeout_while(exited_guard, timeout, |exited| {
+                !workers.iter().all(|worker| exited.contains(&worker.id))
+            })
+            .unwrap();
+        let exited: Vec<usize> = exited_guard.clone();
+        drop(exited_guard);
+
+        let mut all_exited = true;
+        for worker in workers.iter_mut() {
+            if exited.contains(&worker.id) {
+                worker.join();
+            } else {
+                all_exited = false;
+                drop(worker.thread.take());
+            }
+        }
+        all_exited
+    }
+
+    /// Changes the number of threads in the pool
+    ///
+    /// When growing, new workers are spawned with the configuration
+    /// the pool was built with, sharing the queues of the initial workers
+    /// rather than owning one. When shrinking, the first workers to be
+    /// free are asked to shut down before picking up another job, so this
+    /// method blocks until they have finished their current job and have
+    /// been joined
+    ///
+    /// # Arguments
+    ///
+    /// * `num_threads` - The new number of threads in the pool,
+    ///   if `num_threads == 0`, returns an `Err`
+    ///
+    /// If the pool has been shut down or a new worker thread can not be
+    /// spawned, returns an `Err`. In the latter case, the workers spawned
+    /// so far are kept
+    ///
+    /// The workers that have died, e.g. from a panicking worker init, are
+    /// first dropped from the pool without panicking, so that the pool is
+    /// resized from the number of workers still running. The locks they
+    /// poisoned are recovered, so that resizing a pool whose workers all
+    /// died in `PanicMode::Abort` brings it back to life
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashSet;
+    /// use std::sync::{mpsc, Arc, Barrier};
+    /// use std::thread;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///
+    ///     // Six jobs can only pass the barrier together if the pool
+    ///     // has six threads
+    ///     pool.set_num_threads(6).unwrap();
+    ///     let barrier = Arc::new(Barrier::new(6));
+    ///     for _ in 0..6 {
+    ///         let barrier = Arc::clone(&barrier);
+    ///         pool.execute(move || {
+    ///             barrier.wait();
+    ///         }).unwrap();
+    ///     }
+    ///     pool.join();
+    ///
+    ///     pool.set_num_threads(2).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     for _ in 0..100 {
+    ///         let sender = sender.clone();
+    ///         pool.execute(move || sender.send(thread::current().id()).unwrap()).unwrap();
+    ///     }
+    ///     drop(sender);
+    ///     let threads: HashSet<_> = receiver.iter().collect();
+    ///     assert!(threads.len() <= 2);
+    ///
+    ///     assert!(pool.set_num_threads(0).is_err());
+    /// }
+    /// ```
+    ///
+    /// A dead worker does not count towards the size of the pool
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPoolBuilder;
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(3)
+    ///         .with_worker_init(|id| assert_ne!(id, 1))
+    ///         .build()
+    ///         .unwrap();
+    ///     while pool.workers().all(|worker| !worker.is_finished) {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///
+    ///     pool.set_num_threads(2).unwrap();
+    ///     let ids: Vec<usize> = pool.workers().map(|worker| worker.id).collect();
+    ///     assert_eq!(ids, [0, 2]);
+    ///
+    ///     pool.set_num_threads(1).unwrap();
+    ///     assert_eq!(pool.thread_count(), 1);
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || sender.send("Resized").unwrap()).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), "Resized");
+    /// }
+    /// ```
+    ///
+    /// Resizing a dead pool spawns new workers in place of the dead ones
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.workers().any(|worker| !worker.is_finished) {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///     assert!(pool.try_execute(|| {}).is_err());
+    ///
+    ///     pool.set_num_threads(2).unwrap();
+    ///     assert_eq!(pool.thread_count(), 2);
+    ///     assert!(pool.is_healthy());
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || sender.send("Revived").unwrap()).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), "Revived");
+    ///     pool.join();
+    ///     assert!(pool.is_healthy());
+    /// }
+    /// ```
+    pub fn set_num_threads(&self, num_threads: usize) -> Result<(), PoolError> {
+        if num_threads == 0 {
+            return Err(PoolError::InvalidThreadCount(num_threads));
+        }
+        if self.inner.shared.is_closed() {
+            return Err(PoolError::Shutdown);
+        }
+
+        let mut workers = self.inner.workers.lock().unwrap();
+        // The IDs left by the dead workers would otherwise be taken
+        // for the workers terminated below
+        let exited = mem::take(&mut *self.inner.shared.exited.lock().unwrap());
+        let before = workers.len();
+        workers.retain_mut(|worker| {
+            if exited.contains(&worker.id) || !worker.is_running() {
+                worker.try_join();
+                false
+            } else {
+                true
+            }
+        });
+        if workers.len() < before {
+            // The locks poisoned by the dead workers, e.g. in
+            // `PanicMode::Abort`, would kill the new ones right away
+            self.inner.shared.clear_poison();
+        }
+        self.inner
+            .thread_count
+            .store(workers.len(), Ordering::Relaxed);
+        self.inner.spawn_limit.store(num_threads, Ordering::Relaxed);
+        let current = workers.len();
+        if num_threads > current {
+            for _ in current..num_threads {
+                let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+                let thread_builder = self.inner.builder.thread_builder(id);
+                let worker = Worker::new(id, self.inner.shared.clone(), thread_builder)
+                    .map_err(PoolError::SpawnFailed)?;
+                workers.push(worker);
+                self.inner
+                    .thread_count
+                    .store(workers.len(), Ordering::Relaxed);
+            }
+        } else if num_threads < current {
+            self.inner.shared.terminate_workers(current - num_threads);
+
+            // Released before joining, as the other workers record
+            // their exit in it
+            let exited: Vec<usize> = {
+                let mut exited = self.inner.shared.exited.lock().unwrap();
+                while exited.len() < current - num_threads {
+                    exited = self.inner.shared.worker_exited.wait(exited).unwrap();
+                }
+                exited.drain(..).collect()
+            };
+
+            workers.retain_mut(|worker| {
+                if exited.contains(&worker.id) {
+                    worker.try_join();
+                    false
+                } else {
+                    true
+                }
+            });
+            self.inner
+                .thread_count
+                .store(workers.len(), Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the pool with a fresh one built from the same configuration,
+    /// after tearing the current one down
+    ///
+    /// This lets a pool recover after its workers died, e.g. from a job
+    /// panicking in `PanicMode::Abort`. The locks poisoned by the panic are
+    /// recovered, the jobs still queued are executed by the workers still
+    /// alive or dropped otherwise, and the workers that panicked are joined
+    /// without panicking. The new pool has the configured number of threads,
+    /// its worker IDs start at 0 again and its counters are reset
+    ///
+    /// If a worker thread can not be spawned, returns an `Err` and keeps
+    /// the current pool. A pool with a custom job queue is shut down
+    /// before the new one is built, so it is kept shut down
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let mut pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.is_healthy() {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///
+    ///     pool.restart().unwrap();
+    ///     assert!(pool.is_healthy());
+    ///     assert_eq!(pool.thread_count(), 2);
+    ///
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.execute(move || sender.send("Recovered").unwrap()).unwrap();
+    ///     assert_eq!(receiver.recv().unwrap(), "Recovered");
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// Only this handle is moved to the new pool. The other clones of the
+    /// pool, and the `PoolHandle`s given to its jobs, keep referring to the
+    /// old one, which is shut down
+    pub fn restart(&mut self) -> Result<(), PoolError> {
+        if self.inner.builder.job_queue.is_some() {
+            // The old workers drain the custom queue before the new ones
+            // start sharing it
+            self.inner.tear_down();
+            *self = self.inner.builder.clone().build()?;
+            return Ok(());
+        }
+        let pool = self.inner.builder.clone().build()?;
+        let old = mem::replace(self, pool);
+        old.inner.tear_down();
+        Ok(())
+    }
+
+    /// Send a job computing a value to the thread pool and returns
+    /// a `Receiver` on which the value is sent once the job finishes
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() -> T + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let result = pool.submit(|| 6 * 7);
+    ///     assert_eq!(result.recv().unwrap(), 42);
+    /// }
+    /// ```
+    ///
+    /// If the job panics, no value is ever sent and `recv` returns an `Err`
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let result = pool.submit(|| -> i32 { panic!("Error") });
+    ///     assert!(result.recv().is_err());
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// A job blocking on the `Receiver` of a job it submitted to the same
+    /// pool deadlocks the pool once every worker waits like it, with the
+    /// awaited jobs left in the queue. Waiting through `ThreadPool::recv`
+    /// rather than `Receiver::recv` logs a warning about it in debug builds
+    pub fn submit<F, T>(&self, job: F) -> mpsc::Receiver<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_sender, result_receiver) = mpsc::channel();
+        // If the job panics, the sender is dropped while unwinding,
+        // which disconnects the receiver
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            let _ = result_sender.send(job());
+        });
+        result_receiver
+    }
+
+    /// Send a job computing a value to the thread pool and returns
+    /// a `PoolJoinHandle` for waiting on that job alone, like the
+    /// `JoinHandle` returned by `std::thread::spawn`
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() -> T + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let handle = pool.spawn_handle(|| 6 * 7);
+    ///     assert_eq!(handle.join().unwrap(), 42);
+    ///
+    ///     let handle = pool.spawn_handle(|| -> i32 { panic!("Error") });
+    ///     let payload = handle.join().unwrap_err();
+    ///     assert_eq!(payload.downcast_ref::<&str>(), Some(&"Error"));
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// The panic of the job is handed to the handle, so it is neither
+    /// counted by `panic_count` nor passed to the panic handler
+    ///
+    /// Like with `submit`, a job joining the handle of a job of the same
+    /// pool can deadlock it, which `PoolJoinHandle::join` warns about
+    /// in debug builds, see `ThreadPool::recv`
+    pub fn spawn_handle<F, T>(&self, job: F) -> PoolJoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_sender, result_receiver) = mpsc::sync_channel(1);
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            let _ = result_sender.send(panic::catch_unwind(AssertUnwindSafe(job)));
+        });
+        PoolJoinHandle {
+            result: result_receiver,
+            pool: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Sends every job to the thread pool and returns a `ResultStream`
+    /// yielding their results in the order they finish
+    ///
+    /// A job panicking yields the payload of its panic as an `Err`,
+    /// which is neither counted by `panic_count` nor passed to the panic
+    /// handler. The stream ends once every job has finished, or earlier
+    /// if some are dropped without being run, e.g. by a shutdown
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - The callables implementing `FnOnce() -> T + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(3).unwrap();
+    ///     let jobs: Vec<_> = [300, 10, 150]
+    ///         .into_iter()
+    ///         .map(|millis| {
+    ///             move || {
+    ///                 thread::sleep(Duration::from_millis(millis));
+    ///                 millis
+    ///             }
+    ///         })
+    ///         .collect();
+    ///
+    ///     let mut stream = pool.submit_stream(jobs);
+    ///     assert_eq!(stream.next().unwrap().unwrap(), 10);
+    ///     assert_eq!(stream.count(), 2);
+    /// }
+    /// ```
+    pub fn submit_stream<F, T>(&self, jobs: Vec<F>) -> ResultStream<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_sender, result_receiver) = mpsc::channel();
+        for job in jobs {
+            let result_sender = result_sender.clone();
+            self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+                let _ = result_sender.send(panic::catch_unwind(AssertUnwindSafe(job)));
+            });
+        }
+        ResultStream {
+            results: result_receiver,
+        }
+    }
+
+    /// Send a job computing a value to the thread pool and returns
+    /// a future resolving to the value once the job finishes
+    ///
+    /// This lets async code offload blocking work to the pool. The
+    /// future does not need to be polled for the job to run
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - A callable implementing `FnOnce() -> T + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let result = block_on(pool.spawn_async(|| 6 * 7));
+    ///     assert_eq!(result.unwrap(), 42);
+    ///
+    ///     let result = block_on(pool.spawn_async(|| -> i32 { panic!("Error") }));
+    ///     assert_eq!(result.unwrap_err().message(), Some("Error"));
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// The panic of the job is handed to the future, so it is neither
+    /// counted by `panic_count` nor passed to the panic handler. If the
+    /// pool has been shut down, the future resolves to a `JobPanic`
+    /// stating that the job has been dropped
+    #[cfg(feature = "futures")]
+    pub fn spawn_async<F, T>(
+        &self,
+        job: F,
+    ) -> impl std::future::Future<Output = Result<T, JobPanic>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_sender, result_receiver) = futures::channel::oneshot::channel();
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(job)).map_err(|payload| JobPanic { payload });
+            let _ = result_sender.send(result);
+        });
+        async move {
+            result_receiver.await.unwrap_or_else(|_| {
+                Err(JobPanic {
+                    payload: Box::new("The job has been dropped without being run"),
+                })
+            })
+        }
+    }
+
+    /// Send a future to the thread pool, where a worker drives it to
+    /// completion, blocking while it is pending
+    ///
+    /// The worker runs a simple executor for this single future, parking
+    /// until its waker is woken. It is not an async runtime: the future
+    /// occupies the worker until it completes, and the futures it awaits
+    /// must be woken by some other thread, e.g. a timer or an I/O reactor
+    ///
+    /// # Arguments
+    ///
+    /// * `fut` - A future implementing `Future<Output = ()> + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::future::Future;
+    /// use std::pin::Pin;
+    /// use std::sync::{mpsc, Arc, Mutex};
+    /// use std::task::{Context, Poll, Waker};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    ///
+    /// /// A future completing once a timer thread has slept for a while
+    /// struct Timer {
+    ///     state: Arc<Mutex<(bool, Option<Waker>)>>,
+    /// }
+    ///
+    /// impl Timer {
+    ///     fn new(duration: Duration) -> Timer {
+    ///         let state = Arc::new(Mutex::new((false, None::<Waker>)));
+    ///         let timer_state = Arc::clone(&state);
+    ///         thread::spawn(move || {
+    ///             thread::sleep(duration);
+    ///             let mut state = timer_state.lock().unwrap();
+    ///             state.0 = true;
+    ///             if let Some(waker) = state.1.take() {
+    ///                 waker.wake();
+    ///             }
+    ///         });
+    ///         Timer { state }
+    ///     }
+    /// }
+    ///
+    /// impl Future for Timer {
+    ///     type Output = ();
+    ///
+    ///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    ///         let mut state = self.state.lock().unwrap();
+    ///         if state.0 {
+    ///             return Poll::Ready(());
+    ///         }
+    ///         state.1 = Some(cx.waker().clone());
+    ///         Poll::Pending
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     pool.block_on(async move {
+    ///         Timer::new(Duration::from_millis(20)).await;
+    ///         Timer::new(Duration::from_millis(20)).await;
+    ///         sender.send("Both timers fired").unwrap();
+    ///     });
+    ///     assert_eq!(receiver.recv().unwrap(), "Both timers fired");
+    /// }
+    /// ```
+    #[cfg(feature = "futures")]
+    pub fn block_on<Fut>(&self, fut: Fut)
+    where
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.execute_with_priority(Self::DEFAULT_PRIORITY, move || {
+            futures::executor::block_on(fut)
+        });
+    }
+
+    /// Applies `f` to every item on the thread pool and returns
+    /// the results in the order of the items
+    ///
+    /// Blocks the current thread until every item has been processed
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items to process
+    ///
+    /// * `f` - A callable implementing `Fn(T) -> R + Send + Sync + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let squares = pool.map((0..100).collect(), |x: u64| x * x);
+    ///     assert_eq!(squares, (0..100).map(|x| x * x).collect::<Vec<_>>());
+    ///
+    ///     let empty: Vec<u64> = pool.map(Vec::new(), |x: u64| x * x);
+    ///     assert!(empty.is_empty());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `f` panics on one of the items or the pool has been shut down,
+    /// this method panics
+    ///
+    /// # Caution
+    ///
+    /// When called from within a job of the same pool, the mapped jobs
+    /// need other free workers to run on, or else this method deadlocks.
+    /// In debug builds, a warning is logged once every worker is blocked
+    /// like this, as for `for_each`, `map_reduce` and `scatter_gather`
+    ///
+    /// ```
+    /// use log::{Level, Log, Metadata, Record};
+    /// use std::sync::Mutex;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    ///
+    /// struct CapturingLogger(Mutex<Vec<String>>);
+    ///
+    /// impl Log for CapturingLogger {
+    ///     fn enabled(&self, metadata: &Metadata) -> bool {
+    ///         metadata.level() <= Level::Warn
+    ///     }
+    ///     fn log(&self, record: &Record) {
+    ///         self.0.lock().unwrap().push(record.args().to_string());
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    ///
+    /// fn main() {
+    ///     log::set_logger(&LOGGER).unwrap();
+    ///     log::set_max_level(log::LevelFilter::Warn);
+    ///
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let job_pool = pool.clone();
+    ///     let result = pool.submit(move || job_pool.map(vec![1, 2, 3], |x| x * 2));
+    ///
+    ///     if cfg!(debug_assertions) {
+    ///         while !LOGGER.0.lock().unwrap().iter().any(|record| record.contains("deadlocked")) {
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///     }
+    ///
+    ///     // A second worker runs the mapped jobs, which unblocks the first
+    ///     pool.set_num_threads(2).unwrap();
+    ///     assert_eq!(result.recv().unwrap(), vec![2, 4, 6]);
+    /// }
+    /// ```
+    pub fn map<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        F: Fn(T) -> R + Send + Sync + 'static,
+        T: Send + 'static,
+        R: Send + 'static,
+    {
+        let f = Arc::new(f);
+        let results: Vec<_> = items
+            .into_iter()
+            .map(|item| {
+                let f = Arc::clone(&f);
+                self.submit(move || f(item))
+            })
+            .collect();
+        results
+            .into_iter()
+            .map(|result| {
+                self.wait_for_result(&result)
+                    .expect("A mapped job has panicked or the pool has been shut down")
+            })
+            .collect()
+    }
+
+    /// Applies `map` to every item on the thread pool, then folds the
+    /// results into `init` with `reduce` on the current thread, in the
+    /// order of the items
+    ///
+    /// Only the mapped values cross threads, so the folded value does
+    /// not need to be `Send`. Each result is folded as soon as it and
+    /// the ones before it are ready, overlapping with the mapping of
+    /// the next items
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items to process
+    ///
+    /// * `map` - A callable implementing `Fn(T) -> M + Send + Sync + 'static`
+    ///
+    /// * `init` - The initial value of the fold
+    ///
+    /// * `reduce` - A callable implementing `FnMut(R, M) -> R`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let squares = pool.map_reduce(
+    ///         (0..100u64).collect(),
+    ///         |x| x * x,
+    ///         Rc::new(RefCell::new(Vec::new())),
+    ///         |squares, square| {
+    ///             squares.borrow_mut().push(square);
+    ///             squares
+    ///         },
+    ///     );
+    ///     assert_eq!(*squares.borrow(), (0..100).map(|x| x * x).collect::<Vec<_>>());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Like `map`, if `map` panics on one of the items or the pool has
+    /// been shut down, this method panics
+    pub fn map_reduce<T, M, R, Map, Reduce>(
+        &self,
+        items: Vec<T>,
+        map: Map,
+        init: R,
+        mut reduce: Reduce,
+    ) -> R
+    where
+        Map: Fn(T) -> M + Send + Sync + 'static,
+        Reduce: FnMut(R, M) -> R,
+        T: Send + 'static,
+        M: Send + 'static,
+    {
+        let map = Arc::new(map);
+        let results: Vec<_> = items
+            .into_iter()
+            .map(|item| {
+                let map = Arc::clone(&map);
+                self.submit(move || map(item))
+            })
+            .collect();
+        results.into_iter().fold(init, |acc, result| {
+            let value = self
+                .wait_for_result(&result)
+                .expect("A mapped job has panicked or the pool has been shut down");
+            reduce(acc, value)
+        })
+    }
+
+    /// Sorts `data` with a parallel merge sort, blocking until it is sorted
+    ///
+    /// `data` is split into one run per worker, which are sorted on the
+    /// workers and then merged pairwise, the merges of a round running
+    /// in parallel as well. The sort is stable. Slices shorter than
+    /// `ThreadPool::PAR_SORT_THRESHOLD` are sorted on the calling thread
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The vector to sort in place
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let mut seed: u64 = 42;
+    ///     for len in [0, 1, 2, 100, 4095, 4096, 10_000, 100_003] {
+    ///         let mut data: Vec<u64> = (0..len)
+    ///             .map(|_| {
+    ///                 seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+    ///                 (seed >> 33) % 1000
+    ///             })
+    ///             .collect();
+    ///         let mut expected = data.clone();
+    ///         expected.sort();
+    ///
+    ///         pool.par_sort(&mut data);
+    ///         assert_eq!(data, expected);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If comparing two elements panics, or the pool has been shut down or
+    /// its workers have died, this method panics, leaving `data` empty
+    ///
+    /// # Caution
+    ///
+    /// When called from within a job of the same pool, the sorting jobs
+    /// need other free workers to run on, or else this method deadlocks
+    pub fn par_sort<T: Ord + Send>(&self, data: &mut Vec<T>) {
+        const UNSORTED: &str = "The pool has been shut down or its workers have died";
+        let threads = self.thread_count();
+        if data.len() < Self::PAR_SORT_THRESHOLD || threads < 2 {
+            data.sort();
+            return;
+        }
+
+        let run_len = data.len().div_ceil(threads);
+        let mut items = mem::take(data).into_iter();
+        let mut runs: Vec<Vec<T>> = (0..threads)
+            .map(|_| items.by_ref().take(run_len).collect())
+            .collect();
+        self.scope(|scope| {
+            for run in &mut runs {
+                scope.execute(move || run.sort()).expect(UNSORTED);
+            }
+        });
+
+        while runs.len() > 1 {
+            let mut pairs = Vec::with_capacity(runs.len().div_ceil(2));
+            let mut runs_iter = runs.into_iter();
+            while let Some(left) = runs_iter.next() {
+                pairs.push((left, runs_iter.next().unwrap_or_default(), Vec::new()));
+            }
+            self.scope(|scope| {
+                for (left, right, merged) in &mut pairs {
+                    scope
+                        .execute(move || *merged = merge(mem::take(left), mem::take(right)))
+                        .expect(UNSORTED);
+                }
+            });
+            runs = pairs.into_iter().map(|(_, _, merged)| merged).collect();
+        }
+        *data = runs.pop().unwrap_or_default();
+    }
+
+    /// Sends every job to the thread pool and returns their results
+    /// in the order of the jobs
+    ///
+    /// Blocks the current thread until every job has finished
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - The callables implementing `FnOnce() -> R + Send + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///
+    ///     // The first jobs finish last, the results are still in order
+    ///     let jobs: Vec<_> = (0..8u64)
+    ///         .map(|i| {
+    ///             move || {
+    ///                 thread::sleep(Duration::from_millis(10 * (8 - i)));
+    ///                 i
+    ///             }
+    ///         })
+    ///         .collect();
+    ///     assert_eq!(pool.scatter_gather(jobs), (0..8).collect::<Vec<_>>());
+    ///
+    ///     let jobs: Vec<_> = (0..10_000u64).map(|i| move || i * 2).collect();
+    ///     assert_eq!(pool.scatter_gather(jobs), (0..10_000).map(|i| i * 2).collect::<Vec<_>>());
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Like `map`, if one of the jobs panics or the pool has been shut down,
+    /// this method panics once every other job has finished, discarding
+    /// their results. Jobs returning a `Result` let the caller keep the
+    /// successful ones instead
+    ///
+    /// ```should_panic
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let jobs: Vec<Box<dyn FnOnce() -> i32 + Send>> = vec![Box::new(|| 1), Box::new(|| panic!("Error"))];
+    ///     pool.scatter_gather(jobs);
+    /// }
+    /// ```
+    pub fn scatter_gather<R, F>(&self, jobs: Vec<F>) -> Vec<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let results: Vec<_> = jobs.into_iter().map(|job| self.submit(job)).collect();
+        let results: Vec<_> = results
+            .into_iter()
+            .map(|result| self.wait_for_result(&result))
+            .collect();
+        results
+            .into_iter()
+            .map(|result| result.expect("A job has panicked or the pool has been shut down"))
+            .collect()
+    }
+
+    /// Calls `f` on every item on the thread pool
+    ///
+    /// The items are split into one chunk per thread, each processed by
+    /// a single job, which is cheaper than executing a job per item.
+    /// Blocks the current thread until every item has been processed
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items to process
+    ///
+    /// * `f` - A callable implementing `Fn(&T) + Send + Sync + 'static`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(3).unwrap();
+    ///     let visits: Arc<Vec<AtomicUsize>> = Arc::new((0..10_000).map(|_| AtomicUsize::new(0)).collect());
+    ///
+    ///     let job_visits = Arc::clone(&visits);
+    ///     pool.for_each((0..10_000).collect(), move |&i: &usize| {
+    ///         job_visits[i].fetch_add(1, Ordering::SeqCst);
+    ///     });
+    ///     assert!(visits.iter().all(|count| count.load(Ordering::SeqCst) == 1));
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `f` panics on one of the items or the pool has been shut down,
+    /// this method panics
+    pub fn for_each<T, F>(&self, items: Vec<T>, f: F)
+    where
+        F: Fn(&T) + Send + Sync + 'static,
+        T: Send + Sync + 'static,
+    {
+        if items.is_empty() {
+            return;
+        }
+        let chunk_count = self.thread_count().min(items.len());
+        let chunk_size = items.len().div_ceil(chunk_count);
+
+        // Chunks are split off the end, so that no item is moved twice,
+        // the first chunk holding the remainder
+        let mut items = items;
+        let f = Arc::new(f);
+        let mut results = Vec::with_capacity(chunk_count);
+        while !items.is_empty() {
+            let chunk = items.split_off(items.len().saturating_sub(chunk_size));
+            let f = Arc::clone(&f);
+            results.push(self.submit(move || chunk.iter().for_each(|item| f(item))));
+        }
+        for result in results {
+            self.wait_for_result(&result)
+                .expect("A job has panicked or the pool has been shut down");
+        }
+    }
+
+    /// Blocks until the job that `result` was returned for by `submit`
+    /// sends its value, like `Receiver::recv`
+    ///
+    /// In debug builds, a job of the pool waiting here counts as a blocked
+    /// worker. If every worker is blocked while jobs are queued, none of
+    /// them can run the awaited jobs, so a warning is logged about the
+    /// deadlock, as `map`, `for_each`, `map_reduce` and `scatter_gather` do
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The `Receiver` returned by `submit`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::{Level, Log, Metadata, Record};
+    /// use std::sync::Mutex;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    ///
+    /// struct CapturingLogger(Mutex<Vec<String>>);
+    ///
+    /// impl Log for CapturingLogger {
+    ///     fn enabled(&self, metadata: &Metadata) -> bool {
+    ///         metadata.level() <= Level::Warn
+    ///     }
+    ///     fn log(&self, record: &Record) {
+    ///         self.0.lock().unwrap().push(record.args().to_string());
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    ///
+    /// fn deadlock_logged() -> bool {
+    ///     let records = LOGGER.0.lock().unwrap();
+    ///     records.iter().any(|record| record.contains("deadlocked"))
+    /// }
+    ///
+    /// fn main() {
+    ///     log::set_logger(&LOGGER).unwrap();
+    ///     log::set_max_level(log::LevelFilter::Warn);
+    ///
+    ///     // The only worker waits on a job queued behind it
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let job_pool = pool.clone();
+    ///     let result = pool.submit(move || {
+    ///         let inner = job_pool.submit(|| 6 * 7);
+    ///         job_pool.recv(&inner).unwrap()
+    ///     });
+    ///     if cfg!(debug_assertions) {
+    ///         while !deadlock_logged() {
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///     }
+    ///     pool.set_num_threads(2).unwrap();
+    ///     assert_eq!(pool.recv(&result).unwrap(), 42);
+    ///
+    ///     // Joining a `PoolJoinHandle` is checked the same way
+    ///     LOGGER.0.lock().unwrap().clear();
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     let job_pool = pool.clone();
+    ///     let handle = pool.spawn_handle(move || job_pool.spawn_handle(|| 6 * 7).join().unwrap());
+    ///     if cfg!(debug_assertions) {
+    ///         while !deadlock_logged() {
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///     }
+    ///     pool.set_num_threads(2).unwrap();
+    ///     assert_eq!(handle.join().unwrap(), 42);
+    /// }
+    /// ```
+    pub fn recv<T>(&self, result: &mpsc::Receiver<T>) -> Result<T, mpsc::RecvError> {
+        self.wait_for_result(result)
+    }
+
+    /// Blocks until a job submitted by the pool's own methods sends its
+    /// result, see `recv`
+    ///
+    /// In debug builds, a job of the pool waiting on `result` counts as
+    /// a blocked worker. If every worker is blocked while jobs are queued,
+    /// none of them can run, so a warning is logged about the deadlock
+    fn wait_for_result<T>(&self, result: &mpsc::Receiver<T>) -> Result<T, mpsc::RecvError> {
+        let shared = &self.inner.shared;
+        if !cfg!(debug_assertions) || !shared.is_current() {
+            return result.recv();
+        }
+        match result.try_recv() {
+            Ok(value) => return Ok(value),
+            Err(mpsc::TryRecvError::Disconnected) => return Err(mpsc::RecvError),
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+
+        let _blocked = BlockedWorker::new(shared);
+        let blocked = shared.blocked_workers.load(Ordering::SeqCst);
+        if blocked >= self.thread_count() && shared.queued_count.load(Ordering::SeqCst) > 0 {
+            log::warn!(
+                "All {} workers of the thread pool are blocked waiting on queued jobs \
+                 of the same pool, which none of them can run: the pool is deadlocked",
+                blocked
+            );
+        }
+        result.recv()
+    }
+
+    /// Blocks the current thread until all the jobs submitted before
+    /// the call have finished
+    ///
+    /// The pool stays alive, so more jobs can be submitted afterwards.
+    /// Jobs submitted by other threads while waiting are waited for as well
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let counter = Arc::new(AtomicUsize::new(0));
+    ///     for _ in 0..8 {
+    ///         let counter = Arc::clone(&counter);
+    ///         pool.execute(move || {
+    ///             thread::sleep(Duration::from_millis(50));
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///         }).unwrap();
+    ///     }
+    ///     pool.join();
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 8);
+    ///
+    ///     let counter_clone = Arc::clone(&counter);
+    ///     pool.execute(move || {
+    ///         counter_clone.fetch_add(1, Ordering::SeqCst);
+    ///     }).unwrap();
+    ///     pool.join();
+    ///     assert_eq!(counter.load(Ordering::SeqCst), 9);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// A job waiting for itself to finish would deadlock, so when called
+    /// from within a job of the same pool, this method returns immediately
+    /// without waiting
+    ///
+    /// ```
+    /// use std::sync::{mpsc, Arc};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = Arc::new(ThreadPool::build(1).unwrap());
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let pool_clone = Arc::clone(&pool);
+    ///     pool.execute(move || {
+    ///         pool_clone.join();
+    ///         drop(pool_clone);
+    ///         sender.send(()).unwrap();
+    ///     }).unwrap();
+    ///     receiver.recv().unwrap();
+    /// }
+    /// ```
+    pub fn join(&self) {
+        if self.inner.shared.is_current() {
+            return;
+        }
+
+        self.inner.shared.wait_all_done(None);
+    }
+
+    /// Blocks the current thread until all the jobs submitted to every
+    /// pool of `pools` before the call have finished, see `join`
+    ///
+    /// Each pool is joined on a thread of its own, so that a new job
+    /// sent to a pool already joined is still waited for while
+    /// the other pools drain
+    ///
+    /// # Arguments
+    ///
+    /// * `pools` - The pools to join
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pools: Vec<ThreadPool> = (0..3).map(|_| ThreadPool::build(2).unwrap()).collect();
+    ///     let finished = Arc::new(AtomicUsize::new(0));
+    ///     for (i, pool) in pools.iter().enumerate() {
+    ///         for _ in 0..4 {
+    ///             let finished = Arc::clone(&finished);
+    ///             pool.execute(move || {
+    ///                 thread::sleep(Duration::from_millis(20 * (i as u64 + 1)));
+    ///                 finished.fetch_add(1, Ordering::SeqCst);
+    ///             }).unwrap();
+    ///         }
+    ///     }
+    ///
+    ///     let pools: Vec<&ThreadPool> = pools.iter().collect();
+    ///     ThreadPool::join_all(&pools);
+    ///     assert_eq!(finished.load(Ordering::SeqCst), 12);
+    ///     assert!(pools.iter().all(|pool| pool.queued_count() == 0 && pool.active_count() == 0));
+    /// }
+    /// ```
+    pub fn join_all(pools: &[&ThreadPool]) {
+        thread::scope(|scope| {
+            // Like `join`, a job does not wait for its own pool, which
+            // it would do from another thread
+            for pool in pools.iter().filter(|pool| !pool.inner.shared.is_current()) {
+                scope.spawn(|| pool.join());
+            }
+        });
+    }
+
+    /// Blocks until the pool has neither queued nor active jobs, or until
+    /// `timeout` has elapsed, returns whether the pool became idle
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time to wait for
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     for i in 0..6 {
+    ///         pool.execute(move || thread::sleep(Duration::from_millis(10 * i))).unwrap();
+    ///     }
+    ///     assert!(!pool.wait_for_idle(Duration::from_millis(1)));
+    ///
+    ///     assert!(pool.wait_for_idle(Duration::from_secs(10)));
+    ///     assert_eq!(pool.queued_count(), 0);
+    ///     assert_eq!(pool.active_count(), 0);
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// Unlike `join`, this method waits when called from within a job of
+    /// the same pool, and can only return `false` as the job itself is active
+    pub fn wait_for_idle(&self, timeout: Duration) -> bool {
+        self.inner.shared.wait_all_done(Some(timeout))
+    }
+
+    /// Runs `f` on the current thread with this pool installed, so that
+    /// `current_pool` returns it, e.g. for helpers nested deep inside `f`
+    /// to send jobs to it without having it passed down
+    ///
+    /// The previously installed pool, if any, is restored once `f` returns
+    /// or panics. The jobs sent from `f` do not see the pool as installed,
+    /// they can be given a `PoolHandle` through `execute_with_handle`
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A callable implementing `FnOnce() -> R`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::panic::{self, AssertUnwindSafe};
+    /// use std::sync::mpsc;
+    /// use threadpool::{current_pool, ThreadPool};
+    ///
+    /// fn nested_helper(sender: mpsc::Sender<&'static str>) {
+    ///     let pool = current_pool().expect("A pool should be installed");
+    ///     pool.execute(move || sender.send("Sent to the installed pool").unwrap()).unwrap();
+    /// }
+    ///
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let other = ThreadPool::build(3).unwrap();
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let result = pool.install(|| {
+    ///         nested_helper(sender);
+    ///         other.install(|| assert_eq!(current_pool().unwrap().thread_count(), 3));
+    ///         current_pool().unwrap().thread_count()
+    ///     });
+    ///     assert_eq!(result, 2);
+    ///     assert_eq!(receiver.recv().unwrap(), "Sent to the installed pool");
+    ///     assert!(current_pool().is_none());
+    ///
+    ///     let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///         pool.install(|| panic!("Error"));
+    ///     }));
+    ///     assert!(panicked.is_err());
+    ///     assert!(current_pool().is_none());
+    /// }
+    /// ```
+    pub fn install<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let previous = INSTALLED_POOL.with(|pool| pool.replace(Some(self.clone())));
+        let _guard = InstallGuard { previous };
+        f()
+    }
+
+    /// Creates a scope for executing jobs that borrow data from
+    /// the enclosing stack frame, similar to `std::thread::scope`
+    ///
+    /// The closure receives a `Scope` whose `execute` accepts jobs
+    /// that are not `'static`. This method blocks until every job
+    /// executed through the scope has finished before returning
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A callable receiving the `Scope`, its return value
+    ///   is returned by this method
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let mut numbers = [1, 2, 3, 4, 5, 6, 7, 8];
+    ///     let factor = 10;
+    ///     pool.scope(|s| {
+    ///         for chunk in numbers.chunks_mut(2) {
+    ///             s.execute(|| {
+    ///                 for number in chunk {
+    ///                     *number *= factor;
+    ///                 }
+    ///             }).unwrap();
+    ///         }
+    ///     });
+    ///     assert_eq!(numbers, [10, 20, 30, 40, 50, 60, 70, 80]);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `f` or one of the scoped jobs panicked, this method panics
+    /// once every scoped job has finished
+    ///
+    /// ```should_panic
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     pool.scope(|s| s.execute(|| panic!("Error")).unwrap());
+    /// }
+    /// ```
+    ///
+    /// # Caution
+    ///
+    /// When called from within a job of the same pool, the scoped jobs
+    /// need other free workers to run on, or else this method deadlocks
+    pub fn scope<'env, F, R>(&self, f: F) -> R
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> R,
+    {
+        let scope = Scope {
+            pool: self,
+            state: Arc::new(ScopeState {
+                pending: Mutex::new(0),
+                all_done: Condvar::new(),
+                a_job_panicked: AtomicBool::new(false),
+            }),
+            scope: PhantomData,
+            env: PhantomData,
+        };
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+
+        let mut pending = scope.state.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = scope.state.all_done.wait(pending).unwrap();
+        }
+        drop(pending);
+
+        match result {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(_) if scope.state.a_job_panicked.load(Ordering::Relaxed) => {
+                panic!("A scoped job has panicked")
+            }
+            Ok(result) => result,
+        }
+    }
+
+    /// Returns the number of worker threads in the pool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(3).unwrap();
+    ///     assert_eq!(pool.thread_count(), 3);
+    ///
+    ///     pool.set_num_threads(5).unwrap();
+    ///     assert_eq!(pool.thread_count(), 5);
+    ///     pool.set_num_threads(1).unwrap();
+    ///     assert_eq!(pool.thread_count(), 1);
+    /// }
+    /// ```
+    pub fn thread_count(&self) -> usize {
+        self.inner.thread_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the name of the pool, if it has been given one
+    /// by `ThreadPoolBuilder::name`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::{ThreadPool, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new().name(String::from("io")).build().unwrap();
+    ///     assert_eq!(pool.name(), Some("io"));
+    ///     assert_eq!(ThreadPool::build(1).unwrap().name(), None);
+    /// }
+    /// ```
+    pub fn name(&self) -> Option<&str> {
+        self.inner.builder.name.as_deref()
+    }
+
+    /// Returns whether every worker thread is still running
+    ///
+    /// A worker only stops on its own if it panicked, e.g. because of
+    /// a job panicking in `PanicMode::Abort` or a panicking worker init.
+    /// After a shutdown, this method returns `false` as well
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::panic::{self, AssertUnwindSafe};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     assert!(pool.is_healthy());
+    ///
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.is_healthy() {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///     assert!(!pool.is_healthy());
+    ///
+    ///     // Shutting down a pool whose workers panicked panics as well
+    ///     assert!(panic::catch_unwind(AssertUnwindSafe(|| pool.shutdown())).is_err());
+    /// }
+    /// ```
+    pub fn is_healthy(&self) -> bool {
+        self.inner
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .all(Worker::is_running)
+    }
+
+    /// Sends a no-op job to the thread pool and waits at most `timeout`
+    /// for a worker to run it, e.g. for a liveness check
+    ///
+    /// Returns `false` if no worker ran the job in time, meaning that every
+    /// worker is dead or busy, or if the job was rejected as the bounded
+    /// queue is full or the pool has been shut down. A late job still
+    /// runs, with no effect
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum amount of time to wait for
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::time::{Duration, Instant};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     assert!(pool.ping(Duration::from_secs(5)));
+    ///
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     for _ in 0..2 {
+    ///         let started_sender = started_sender.clone();
+    ///         pool.execute(move || {
+    ///             started_sender.send(()).unwrap();
+    ///             std::thread::sleep(Duration::from_millis(500));
+    ///         }).unwrap();
+    ///     }
+    ///     started.recv().unwrap();
+    ///     started.recv().unwrap();
+    ///
+    ///     let start = Instant::now();
+    ///     assert!(!pool.ping(Duration::from_millis(50)));
+    ///     assert!(start.elapsed() < Duration::from_millis(400));
+    /// }
+    /// ```
+    pub fn ping(&self, timeout: Duration) -> bool {
+        let (sender, receiver) = mpsc::channel();
+        let sent = self.try_execute(move || {
+            let _ = sender.send(());
+        });
+        sent.is_ok() && receiver.recv_timeout(timeout).is_ok()
+    }
+
+    /// Returns an iterator over a snapshot of the pool's workers,
+    /// in the order they were spawned
+    ///
+    /// The snapshot is taken when this method is called, so workers
+    /// spawned or stopped afterwards are not reflected
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(3).unwrap();
+    ///     let ids: Vec<usize> = pool.workers().map(|worker| worker.id).collect();
+    ///     assert_eq!(ids, vec![0, 1, 2]);
+    ///     assert!(pool.workers().all(|worker| !worker.is_finished));
+    ///
+    ///     pool.set_num_threads(4).unwrap();
+    ///     assert_eq!(pool.workers().count(), 4);
+    /// }
+    /// ```
+    pub fn workers(&self) -> impl Iterator<Item = WorkerInfo> + '_ {
+        let workers = self.inner.workers.lock().unwrap();
+        let snapshot: Vec<WorkerInfo> = workers
+            .iter()
+            .map(|worker| WorkerInfo {
+                id: worker.id,
+                is_finished: !worker.is_running(),
+            })
+            .collect();
+        snapshot.into_iter()
+    }
+
+    /// Returns the number of jobs waiting for a worker to pick them up
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Barrier};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let started = Arc::new(Barrier::new(3));
+    ///     let finish = Arc::new(Barrier::new(3));
+    ///     for _ in 0..2 {
+    ///         let started = Arc::clone(&started);
+    ///         let finish = Arc::clone(&finish);
+    ///         pool.execute(move || {
+    ///             started.wait();
+    ///             finish.wait();
+    ///         }).unwrap();
+    ///     }
+    ///     started.wait();
+    ///
+    ///     // Both workers are blocked, so the next jobs have to wait
+    ///     for _ in 0..5 {
+    ///         pool.execute(|| {}).unwrap();
+    ///     }
+    ///     assert_eq!(pool.queued_count(), 5);
+    ///
+    ///     finish.wait();
+    ///     pool.join();
+    ///     assert_eq!(pool.queued_count(), 0);
+    /// }
+    /// ```
+    pub fn queued_count(&self) -> usize {
+        let shared = &self.inner.shared;
+        shared.queued_count.load(Ordering::Relaxed) + shared.pinned_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of jobs currently being executed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Barrier};
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let started = Arc::new(Barrier::new(4));
+    ///     let finish = Arc::new(Barrier::new(4));
+    ///     for _ in 0..3 {
+    ///         let started = Arc::clone(&started);
+    ///         let finish = Arc::clone(&finish);
+    ///         pool.execute(move || {
+    ///             started.wait();
+    ///             finish.wait();
+    ///         }).unwrap();
+    ///     }
+    ///
+    ///     started.wait();
+    ///     assert_eq!(pool.active_count(), 3);
+    ///     finish.wait();
+    ///     pool.join();
+    ///     assert_eq!(pool.active_count(), 0);
+    /// }
+    /// ```
+    pub fn active_count(&self) -> usize {
+        self.inner.shared.active_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the labels of the jobs sent through `execute_labeled`
+    /// that are running, ordered by the ID of their worker
+    ///
+    /// The jobs sent without a label are not listed, see `active_count`
+    /// for the number of running jobs
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     let (started_sender, started) = mpsc::channel();
+    ///     let (release, released) = mpsc::channel::<()>();
+    ///     pool.execute_labeled("import", move || {
+    ///         started_sender.send(()).unwrap();
+    ///         released.recv().unwrap();
+    ///     });
+    ///
+    ///     started.recv().unwrap();
+    ///     assert_eq!(pool.active_labels(), ["import"]);
+    ///     release.send(()).unwrap();
+    ///     pool.join();
+    ///     assert!(pool.active_labels().is_empty());
+    /// }
+    /// ```
+    pub fn active_labels(&self) -> Vec<&'static str> {
+        let mut labels: Vec<(usize, &'static str)> = self
+            .inner
+            .shared
+            .labels
+            .lock()
+            .unwrap()
+            .values()
+            .copied()
+            .collect();
+        labels.sort_unstable();
+        labels.into_iter().map(|(_, label)| label).collect()
+    }
+
+    /// Returns the number of jobs the workers have finished so far,
+    /// including the ones that panicked
+    ///
+    /// The count only grows, so sampling it over time gives the throughput
+    /// of the pool. The jobs discarded by `clear_queue` are not counted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     for _ in 0..100 {
+    ///         pool.execute(|| {}).unwrap();
+    ///     }
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     pool.join();
+    ///     assert_eq!(pool.completed_count(), 101);
+    /// }
+    /// ```
+    pub fn completed_count(&self) -> usize {
+        self.inner.shared.completed_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the fraction of time the workers have spent running jobs
+    /// since the pool was built, between 0 and 1
+    ///
+    /// The time the workers could have been busy is the time elapsed
+    /// multiplied by the current thread count, so the fraction is only
+    /// approximate once the pool has been resized. Running jobs are only
+    /// accounted for once they finish
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     for _ in 0..2 {
+    ///         pool.execute(|| thread::sleep(Duration::from_millis(200))).unwrap();
+    ///     }
+    ///     pool.join();
+    ///     thread::sleep(Duration::from_millis(200));
+    ///
+    ///     // Busy for about half of the 400ms elapsed
+    ///     let utilization = pool.utilization();
+    ///     assert!((0.3..0.6).contains(&utilization), "{}", utilization);
+    /// }
+    /// ```
+    pub fn utilization(&self) -> f64 {
+        let shared = &self.inner.shared;
+        let available = shared.created.elapsed().as_secs_f64() * self.thread_count() as f64;
+        if available == 0.0 {
+            return 0.0;
+        }
+        let busy = Duration::from_nanos(shared.busy_nanos.load(Ordering::Relaxed));
+        (busy.as_secs_f64() / available).min(1.0)
+    }
+
+    /// Returns the number of jobs that have panicked so far
+    ///
+    /// The panics are only counted with the `panic = "unwind"` strategy,
+    /// see `CATCHES_PANICS`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     for _ in 0..10 {
+    ///         pool.execute(|| panic!("Error")).unwrap();
+    ///     }
+    ///     while pool.panic_count() < 10 {
+    ///         std::thread::yield_now();
+    ///     }
+    ///     assert_eq!(pool.panic_count(), 10);
+    /// }
+    /// ```
+    pub fn panic_count(&self) -> usize {
+        self.inner.shared.panic_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the pool's counters, sampled back to back
+    ///
+    /// The counters are not read under a common lock, so while jobs are
+    /// flowing through the pool, the snapshot may be a few jobs off.
+    /// It is exact once the pool is quiescent, e.g. after `join`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::{PoolMetrics, ThreadPool};
+    /// fn main() {
+    ///     let pool = ThreadPool::build(2).unwrap();
+    ///     for _ in 0..10 {
+    ///         pool.execute(|| {}).unwrap();
+    ///     }
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     pool.join();
+    ///
+    ///     let metrics = pool.metrics();
+    ///     assert_eq!(
+    ///         metrics,
+    ///         PoolMetrics {
+    ///             thread_count: pool.thread_count(),
+    ///             queued_count: pool.queued_count(),
+    ///             active_count: pool.active_count(),
+    ///             completed_count: pool.completed_count(),
+    ///             panic_count: pool.panic_count(),
+    ///             active_labels: Vec::new(),
+    ///         }
+    ///     );
+    ///     assert_eq!(metrics.completed_count, 11);
+    ///     assert_eq!(metrics.panic_count, 1);
+    /// }
+    /// ```
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            thread_count: self.thread_count(),
+            queued_count: self.queued_count(),
+            active_count: self.active_count(),
+            completed_count: self.completed_count(),
+            panic_count: self.panic_count(),
+            active_labels: self.active_labels().into_iter().map(String::from).collect(),
+        }
+    }
+
+    /// Removes and returns the messages of the jobs that have panicked,
+    /// in the order they panicked
+    ///
+    /// Only the latest `ThreadPool::PANIC_LOG_CAPACITY` messages are kept,
+    /// the oldest being dropped first. A payload that is not a string is
+    /// recorded as `"Box<dyn Any>"`, like the default panic hook does
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(1).unwrap();
+    ///     for i in 0..3 {
+    ///         pool.execute(move || panic!("Error {}", i)).unwrap();
+    ///     }
+    ///     pool.execute(|| std::panic::panic_any(42)).unwrap();
+    ///     pool.join();
+    ///
+    ///     assert_eq!(pool.take_panics(), ["Error 0", "Error 1", "Error 2", "Box<dyn Any>"]);
+    ///     assert!(pool.take_panics().is_empty());
+    /// }
+    /// ```
+    pub fn take_panics(&self) -> Vec<String> {
+        self.inner
+            .shared
+            .panic_log
+            .lock()
+            .unwrap()
+            .drain(..)
+            .collect()
+    }
+}
+
+impl Default for ThreadPool {
+    /// Returns a thread pool with one thread per available CPU
+    ///
+    /// The thread count is given by `std::thread::available_parallelism`,
+    /// falling back to 1 if it can not be queried
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::default();
+    ///     assert_eq!(pool.submit(|| 42).recv().unwrap(), 42);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If not even a single worker thread can be spawned, this method panics
+    fn default() -> Self {
+        ThreadPool::build_nonzero(default_thread_count())
+            .or_else(|_| ThreadPool::build(1))
+            .expect("Failed to spawn a worker thread")
+    }
+}
+
+/// Returns the number of available CPUs, or 1 if it can not be queried
+fn default_thread_count() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+impl fmt::Debug for ThreadPool {
+    /// Formats the name, the thread count, the job counters and
+    /// the health of the pool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(3).unwrap();
+    ///     let debug = format!("{:?}", pool);
+    ///     assert!(debug.contains("thread_count: 3"));
+    ///     assert!(debug.contains("is_healthy: true"));
+    /// }
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadPool")
+            .field("name", &self.name())
+            .field("thread_count", &self.thread_count())
+            .field("queued_count", &self.queued_count())
+            .field("active_count", &self.active_count())
+            .field("completed_count", &self.completed_count())
+            .field("is_healthy", &self.is_healthy())
+            .finish()
+    }
+}
+
+impl Drop for Inner {
+    /// Gracefully shutdown the thread pool once its last handle is dropped
+    ///
+    /// Close the job queue and wait for all threads to shutdown
+    ///
+    /// Panicking jobs do not terminate the workers, so dropping
+    /// a pool that had executed them does not panic
+    ///
+    /// ```
+    /// # use threadpool::ThreadPool;
+    /// fn main() {
+    ///     let pool = ThreadPool::build(10).unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    /// }
+    /// ```
+    ///
+    /// In debug builds, a warning is logged if jobs are still queued,
+    /// as the drop blocks until they have been executed. A pool built
+    /// with `DropPolicy::Abandon` drops them instead, only waiting for
+    /// the running jobs
+    ///
+    /// If one of the workers had panicked and thus, terminated prematurely,
+    /// an error is logged but the drop does not panic, as it may run while
+    /// the thread is already unwinding. Call `shutdown` beforehand to have
+    /// it reported by a panic, or check `is_healthy`
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use threadpool::{PanicMode, ThreadPoolBuilder};
+    /// fn main() {
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(2)
+    ///         .panic_mode(PanicMode::Abort)
+    ///         .build()
+    ///         .unwrap();
+    ///     pool.execute(|| panic!("Error")).unwrap();
+    ///     while pool.is_healthy() {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    ///
+    ///     // Dropping the pool neither panics nor aborts the process
+    ///     drop(pool);
+    /// }
+    /// ```
+    ///
+    /// The logged error lists the IDs of the workers that panicked
+    ///
+    /// ```
+    /// use log::{Level, Log, Metadata, Record};
+    /// use std::sync::Mutex;
+    /// use threadpool::ThreadPoolBuilder;
+    ///
+    /// struct CapturingLogger(Mutex<Vec<String>>);
+    ///
+    /// impl Log for CapturingLogger {
+    ///     fn enabled(&self, _: &Metadata) -> bool {
+    ///         true
+    ///     }
+    ///     fn log(&self, record: &Record) {
+    ///         if record.level() == Level::Error {
+    ///             self.0.lock().unwrap().push(record.args().to_string());
+    ///         }
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    ///
+    /// fn main() {
+    ///     log::set_logger(&LOGGER).unwrap();
+    ///     log::set_max_level(log::LevelFilter::Error);
+    ///
+    ///     let pool = ThreadPoolBuilder::new()
+    ///         .num_threads(3)
+    ///         .with_worker_init(|id| assert_ne!(id, 1, "Worker 1 fails to start"))
+    ///         .build()
+    ///         .unwrap();
+    ///     drop(pool);
+    ///     assert_eq!(
+    ///         *LOGGER.0.lock().unwrap(),
+    ///         ["Some workers of the dropped thread pool have panicked: 1"]
+    ///     );
+    /// }
+    /// ```
+    fn drop(&mut self) {
+        if self.builder.drop_policy == DropPolicy::Abandon {
+            // Closed first, so that no job is queued once the queue
+            // has been cleared. The pool is going away, so the locks
+            // poisoned by a job in `PanicMode::Abort` can be recovered
+            self.close();
+            self.shared.clear_poison();
+            self.shared.clear();
+        } else if cfg!(debug_assertions) {
+            let queued = self.shared.queued_count.load(Ordering::Relaxed);
+            if queued > 0 {
+                log::warn!(
+                    "The thread pool is dropped with {} queued jobs left to drain",
+                    queued
+                );
+            }
+        }
+        let panicked = self.shutdown();
+        if !panicked.is_empty() {
+            let ids: Vec<String> = panicked.iter().map(usize::to_string).collect();
+            log::error!(
+                "Some workers of the dropped thread pool have panicked: {}",
+                ids.join(", ")
+            );
+        }
+    }
+}
+
+/// Merges two sorted vectors into a sorted one, taking from `left`
+/// first on ties so that the merge is stable
+fn merge<T: Ord>(left: Vec<T>, right: Vec<T>) -> Vec<T> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        let next = if r < l { right.next() } else { left.next() };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+/// Returns the message of a panic payload, if it is a string
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => Some(message),
+        None => payload.downcast_ref::<String>().map(String::as_str),
+    }
+}
+
+/// An error handing back a job the thread pool did not accept
+///
+/// ```
+/// use threadpool::JobRejected;
 /// fn main() {
-///     ThreadPool::build(0).unwrap();
+///     let rejected = JobRejected::Full(|| {});
+///     assert_eq!(rejected.to_string(), "The job queue is full");
+///     let job = rejected.into_inner();
+///     job();
+///
+///     let rejected = JobRejected::Shutdown(|| {});
+///     assert_eq!(rejected.to_string(), "The thread pool has been shut down");
+///
+///     let rejected = JobRejected::Dead(|| {});
+///     assert_eq!(rejected.to_string(), "Every worker of the thread pool has died");
 /// }
 /// ```
-#[derive(Debug, Clone)]
-pub struct ThreadCountError {
-    caller: &'static str,
-    invalid_val: usize,
+pub enum JobRejected<F> {
+    /// The queue is bounded and full
+    Full(F),
+    /// The thread pool has been shut down
+    Shutdown(F),
+    /// Every worker of the thread pool has died
+    Dead(F),
 }
 
-impl fmt::Display for ThreadCountError {
+impl<F> JobRejected<F> {
+    /// Returns the rejected job
+    pub fn into_inner(self) -> F {
+        match self {
+            JobRejected::Full(job) | JobRejected::Shutdown(job) | JobRejected::Dead(job) => job,
+        }
+    }
+}
+
+impl<F> fmt::Debug for JobRejected<F> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}: Invalid thread count: {}. Thread count must be a positive number.",
-            self.caller, self.invalid_val
-        )
+        match self {
+            JobRejected::Full(_) => write!(f, "Full(..)"),
+            JobRejected::Shutdown(_) => write!(f, "Shutdown(..)"),
+            JobRejected::Dead(_) => write!(f, "Dead(..)"),
+        }
     }
 }
 
-/// A struct representing a job that can sent to other threads
-/// and can only be called once
-pub type Job = Box<dyn FnOnce() + Send + 'static>;
+impl<F> fmt::Display for JobRejected<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobRejected::Full(_) => write!(f, "The job queue is full"),
+            JobRejected::Shutdown(_) => write!(f, "The thread pool has been shut down"),
+            JobRejected::Dead(_) => write!(f, "Every worker of the thread pool has died"),
+        }
+    }
+}
 
-/// A struct representing a worker holding a thread for executing job
-pub struct Worker {
-    id: usize,
-    thread: Option<JoinHandle<()>>,
+impl<F> std::error::Error for JobRejected<F> {}
+
+/// An error carrying the payload of a panicking job
+pub struct JobPanic {
+    payload: Box<dyn Any + Send>,
 }
 
-impl Worker {
-    /// Returns a new worker with its thread initiated
+impl JobPanic {
+    /// Returns the panic message, if the job panicked with a string
+    pub fn message(&self) -> Option<&str> {
+        panic_message(&*self.payload)
+    }
+
+    /// Returns the payload the job panicked with
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for JobPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JobPanic")
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl fmt::Display for JobPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "The job panicked: {}", message),
+            None => write!(f, "The job panicked"),
+        }
+    }
+}
+
+impl std::error::Error for JobPanic {}
+
+/// A handle passed to the jobs sent through `ThreadPool::execute_with_handle`,
+/// letting them send more jobs to the pool executing them
+///
+/// Unlike a `ThreadPool` clone, the handle does not keep the pool running
+pub struct PoolHandle {
+    shared: Arc<Shared>,
+}
+
+impl PoolHandle {
+    /// Send a job to the thread pool, see `ThreadPool::execute`
     ///
     /// # Arguments
     ///
-    /// * `id` - The worker's ID
-    ///
-    /// * `receiver` - A lock-protected receiver shared with other workers
-    /// within the same thread pool
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    pub fn execute<F>(&self, job: F) -> Result<(), PoolError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.try_push_job(
+            ThreadPool::DEFAULT_PRIORITY,
+            in_submission_span(Box::new(job)),
+        )
+    }
+
+    /// Send a job receiving a handle to the thread pool, see
+    /// `ThreadPool::execute_with_handle`
     ///
-    /// # Caution
+    /// # Arguments
     ///
-    /// If a worker panics, other workers within the pool will panic
-    /// due to mutex poisoning and effectively the thread pool is dead.
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        Worker {
-            id,
-            thread: Some(thread::spawn(move || {
-                eprintln!("Thread {} is starting up", id);
-                loop {
-                    match receiver.lock().unwrap().recv() {
-                        Ok(job) => job(),
-                        Err(_) => {
-                            eprintln!("Thread {} is shutting down", id);
-                            break;
-                        }
-                    }
-                }
-            })),
+    /// * `job` - A callable implementing `FnOnce(&PoolHandle) + Send + 'static`
+    pub fn execute_with_handle<F>(&self, job: F)
+    where
+        F: FnOnce(&PoolHandle) + Send + 'static,
+    {
+        let handle = PoolHandle {
+            shared: Arc::clone(&self.shared),
+        };
+        self.shared.push_job(
+            ThreadPool::DEFAULT_PRIORITY,
+            in_submission_span(Box::new(move || job(&handle))),
+        );
+    }
+}
+
+/// Runs one iteration of a job sent through `ThreadPool::execute_loop`,
+/// requeuing the next one unless the job breaks
+fn run_loop<F>(handle: &PoolHandle, mut job: F)
+where
+    F: FnMut() -> ControlFlow<()> + Send + 'static,
+{
+    if job().is_continue() {
+        handle.execute_with_handle(move |handle| run_loop(handle, job));
+    }
+}
+
+/// A flag shared between a cancellable job and its `JobHandle`, or
+/// between a pool and its jobs, which the job can poll to stop early
+/// once it is cancelled
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Returns whether the job has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// The jobs to submit once a job has finished, `None` once it has
+type Successors = Arc<Mutex<Option<Vec<Job>>>>;
+
+/// A guard submitting the successors of a job when dropped, i.e. once
+/// the job has finished or has been dropped without being run
+struct FinishGuard {
+    successors: Successors,
+    shared: Arc<Shared>,
+}
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        let successors = self.successors.lock().unwrap().take();
+        // A job rejected by a closed pool is dropped under the queue locks,
+        // so its successors are dropped in turn without locking them again
+        if let Some(successors) = successors.filter(|successors| !successors.is_empty()) {
+            if !self.shared.is_closed() {
+                self.shared
+                    .push_jobs(ThreadPool::DEFAULT_PRIORITY, successors);
+            }
         }
     }
 }
 
-/// A struct representing a thread pool
-pub struct ThreadPool {
-    workers: Vec<Worker>,
-    job_sender: Option<mpsc::Sender<Job>>,
+/// A handle to a job executed by `ThreadPool::execute_cancellable`
+/// or `ThreadPool::execute_tracked`
+pub struct JobHandle {
+    token: CancellationToken,
+    successors: Successors,
+    shared: Arc<Shared>,
 }
 
-impl ThreadPool {
-    /// Returns a `Result<ThreadPool, ThreadCountError>`
+impl fmt::Debug for JobHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JobHandle")
+            .field("is_cancelled", &self.is_cancelled())
+            .field("is_finished", &self.is_finished())
+            .finish()
+    }
+}
+
+impl JobHandle {
+    /// Returns a job running `job` unless it is cancelled beforehand,
+    /// then submitting its successors, along with its handle
+    fn wrap<F>(shared: &Arc<Shared>, job: F) -> (Job, JobHandle)
+    where
+        F: FnOnce(CancellationToken) + Send + 'static,
+    {
+        let token = CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let successors: Successors = Arc::new(Mutex::new(Some(Vec::new())));
+        let guard = FinishGuard {
+            successors: Arc::clone(&successors),
+            shared: Arc::clone(shared),
+        };
+        let job_token = token.clone();
+        let job = in_submission_span(Box::new(move || {
+            let _guard = guard;
+            if !job_token.is_cancelled() {
+                job(job_token);
+            }
+        }));
+        let handle = JobHandle {
+            token,
+            successors,
+            shared: Arc::clone(shared),
+        };
+        (job, handle)
+    }
+
+    /// Chains `job` to the job, submitting it to the pool only once
+    /// the job has finished, and returns its own handle
+    ///
+    /// The successor runs even if the job panicked or was cancelled,
+    /// and is submitted right away if the job has already finished.
+    /// If the job is dropped without being run, e.g. by
+    /// `ThreadPool::clear_queue`, its successors are submitted as well
     ///
     /// # Arguments
     ///
-    /// * `thread_count` - The number of threads in the pool,
-    /// if `thread_count < 0`, returns an `Err`
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
     ///
     /// # Examples
     ///
-    /// This code panics
-    ///
-    /// ```should_panic
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{mpsc, Arc};
     /// use threadpool::ThreadPool;
     /// fn main() {
-    ///     ThreadPool::build(0).unwrap();
-    /// }
-    /// ```
+    ///     let pool = ThreadPool::build(4).unwrap();
+    ///     let stage = Arc::new(AtomicUsize::new(0));
+    ///     let (sender, receiver) = mpsc::channel();
     ///
-    /// Negative `thread_count` passed to `build` would not compile.
+    ///     let (a, b, c) = (Arc::clone(&stage), Arc::clone(&stage), Arc::clone(&stage));
+    ///     pool.execute_tracked(move || assert_eq!(a.fetch_add(1, Ordering::SeqCst), 0))
+    ///         .then(move || assert_eq!(b.fetch_add(1, Ordering::SeqCst), 1))
+    ///         .then(move || sender.send(c.load(Ordering::SeqCst)).unwrap());
     ///
-    /// ```compile_fail
-    /// use threadpool::ThreadPool;
-    /// fn main() {
-    ///     ThreadPool::build(-1);
+    ///     assert_eq!(receiver.recv().unwrap(), 2);
     /// }
     /// ```
-    ///
-    pub fn build(thread_count: usize) -> Result<ThreadPool, ThreadCountError> {
-        if thread_count <= 0 {
-            return Err(ThreadCountError {
-                caller: "ThreadPool::new()",
-                invalid_val: thread_count,
-            });
+    pub fn then<F>(self, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let (job, handle) = JobHandle::wrap(&self.shared, move |_| job());
+        let mut successors = self.successors.lock().unwrap();
+        match successors.as_mut() {
+            Some(successors) => successors.push(job),
+            None => {
+                drop(successors);
+                self.shared.push_job(ThreadPool::DEFAULT_PRIORITY, job);
+            }
         }
+        handle
+    }
+
+    /// Returns whether the job has finished, or has been dropped
+    /// without being run
+    pub fn is_finished(&self) -> bool {
+        self.successors.lock().unwrap().is_none()
+    }
 
-        let (job_sender, job_receiver) = mpsc::channel();
-        let job_sender = Some(job_sender);
-        let job_receiver = Arc::new(Mutex::new(job_receiver));
+    /// Cancels the job
+    ///
+    /// If the job has not started yet, it is skipped when a worker picks
+    /// it up. Otherwise, the job is only marked as cancelled, and stops
+    /// early if it polls its `CancellationToken`
+    pub fn cancel(&self) {
+        self.token.cancelled.store(true, Ordering::Release);
+    }
 
-        let mut workers = Vec::with_capacity(thread_count);
+    /// Returns whether the job has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
 
-        for id in 0..thread_count {
-            workers.push(Worker::new(id, job_receiver.clone()));
-        }
+/// When a job sent through `ThreadPool::execute_timeout` started and
+/// finished running
+#[derive(Debug, Default)]
+struct Timing {
+    started: Option<Instant>,
+    finished: Option<Instant>,
+}
 
-        Ok(ThreadPool {
-            workers,
-            job_sender,
-        })
+/// A guard recording when a job finished, even if it panicked
+struct FinishTiming {
+    timing: Arc<Mutex<Timing>>,
+}
+
+impl Drop for FinishTiming {
+    fn drop(&mut self) {
+        if let Ok(mut timing) = self.timing.lock() {
+            timing.finished = Some(Instant::now());
+        }
     }
+}
 
-    /// Send a job to the thread pool to execute it
+/// A handle to a job executed by `ThreadPool::execute_timeout`,
+/// reporting whether the job ran for longer than its timeout
+#[derive(Debug)]
+pub struct TimeoutHandle {
+    timeout: Duration,
+    timing: Arc<Mutex<Timing>>,
+}
+
+impl TimeoutHandle {
+    /// Returns whether the job has been running for longer than its
+    /// timeout, or did so before finishing
     ///
-    /// # Arguments
+    /// A job waiting in the queue has not timed out yet
+    pub fn timed_out(&self) -> bool {
+        let timing = self.timing.lock().unwrap();
+        match (timing.started, timing.finished) {
+            (Some(started), Some(finished)) => finished - started > self.timeout,
+            (Some(started), None) => started.elapsed() > self.timeout,
+            (None, _) => false,
+        }
+    }
+
+    /// Returns whether the job has finished running
+    pub fn is_finished(&self) -> bool {
+        self.timing.lock().unwrap().finished.is_some()
+    }
+}
+
+/// A handle to a job executed by `ThreadPool::spawn_handle`
+#[derive(Debug)]
+pub struct PoolJoinHandle<T> {
+    result: mpsc::Receiver<thread::Result<T>>,
+    /// The pool the job was sent to, held weakly so that the handle does
+    /// not keep it alive, to check a joining job for a deadlock
+    pool: Weak<Inner>,
+}
+
+impl<T> PoolJoinHandle<T> {
+    /// Blocks until the job has finished, returns its value, or the
+    /// payload of its panic as an `Err`
     ///
-    /// * `&mut self`
+    /// If the job has been dropped without being run, e.g. because
+    /// the pool has been shut down, returns an `Err` as well
     ///
-    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    /// A job of the same pool joining the handle is checked for
+    /// a deadlock in debug builds, like by `ThreadPool::recv`
+    pub fn join(self) -> thread::Result<T> {
+        let result = match self.pool.upgrade() {
+            Some(inner) => ThreadPool { inner }.wait_for_result(&self.result),
+            None => self.result.recv(),
+        };
+        result.unwrap_or_else(|_| Err(Box::new("The job has been dropped without being run")))
+    }
+}
+
+/// An iterator over the results of the jobs sent through
+/// `ThreadPool::submit_stream`, in the order they finish
+///
+/// `next` blocks until the next job finishes
+#[derive(Debug)]
+pub struct ResultStream<T> {
+    results: mpsc::Receiver<thread::Result<T>>,
+}
+
+impl<T> Iterator for ResultStream<T> {
+    type Item = thread::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.results.recv().ok()
+    }
+}
+
+/// A handle sending jobs to the thread pool to run one at a time,
+/// in submission order, returned by `ThreadPool::sequential_group`
+///
+/// The jobs of a group run on a single worker, which another one takes
+/// over if it is stopped by `set_num_threads`. The group keeps the pool
+/// alive until it is dropped
+#[derive(Debug)]
+pub struct SequentialGroup {
+    pool: ThreadPool,
+    /// The ID of the worker running the group's jobs
+    worker_id: Mutex<usize>,
+}
+
+impl SequentialGroup {
+    /// Returns the ID of the running worker the next group is sent to
+    fn pick_worker(inner: &Inner, workers: &[Worker]) -> Option<usize> {
+        let running: Vec<usize> = workers
+            .iter()
+            .filter(|worker| worker.is_running())
+            .map(|worker| worker.id)
+            .collect();
+        if running.is_empty() {
+            return None;
+        }
+        let next = inner.next_group.fetch_add(1, Ordering::Relaxed);
+        Some(running[next % running.len()])
+    }
+
+    /// Send a job to the thread pool, to run once the jobs sent to
+    /// the group before it have finished
     ///
-    /// # Caution
+    /// The job is dropped without being run if the pool has been shut down
     ///
-    /// Careful to guarantee that the callable can not panic or else
-    /// the thread pool can possibly be "dead" and will silently stop
-    /// executing job
+    /// # Arguments
     ///
-    /// However, this can be detected if a panic is observed when the
-    /// thread pool is dropped
-    /// by panicking
-    pub fn execute<F>(&mut self, job: F)
+    /// * `job` - A callable implementing `FnOnce() + Send + 'static`
+    pub fn execute<F>(&self, job: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        self.job_sender
-            .as_ref()
-            .unwrap()
-            .send(Box::new(job))
-            .unwrap();
+        let inner = &self.pool.inner;
+        // Holding the lock keeps `set_num_threads` from stopping
+        // the worker before it has picked the job up
+        let mut workers = inner.workers.lock().unwrap();
+        let mut worker_id = self.worker_id.lock().unwrap();
+        if !workers
+            .iter()
+            .any(|worker| worker.id == *worker_id && worker.is_running())
+        {
+            // A stopped worker has run every job pinned to it, so moving
+            // to another one keeps the submission order
+            // A lazily spawning pool may not have spawned a worker yet
+            match Self::pick_worker(inner, &workers).or_else(|| inner.spawn_lazily(&mut workers)) {
+                Some(id) => *worker_id = id,
+                None => return,
+            }
+        }
+        inner
+            .shared
+            .push_pinned(*worker_id, in_submission_span(Box::new(job)));
     }
 }
 
-impl Drop for ThreadPool {
-    /// Gracefully shutdown the thread pool
-    ///
-    /// Drop the job sender and wait for all threads to shutdown
-    ///
-    /// # Panics
+/// A scope for executing jobs that borrow non-`'static` data,
+/// created by `ThreadPool::scope`
+pub struct Scope<'scope, 'env: 'scope> {
+    pool: &'scope ThreadPool,
+    state: Arc<ScopeState>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+/// The state shared between a scope and its jobs
+struct ScopeState {
+    /// The number of scoped jobs that have not finished yet
+    pending: Mutex<usize>,
+    all_done: Condvar,
+    a_job_panicked: AtomicBool,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Send a job borrowing data from the scope to the thread pool,
+    /// returns an `Err` without running it like `ThreadPool::execute`
     ///
-    /// If one of the worker had panicked and thus, terminated prematurely,
-    /// this method panics
+    /// # Arguments
     ///
-    /// ```should_panic
-    /// # use threadpool::ThreadPool;
-    /// fn main() {
-    ///     let mut pool = ThreadPool::build(10).unwrap();
-    ///     pool.execute(|| panic!("Error"));
-    /// }
-    /// ```
+    /// * `job` - A callable implementing `FnOnce() + Send + 'scope`
+    pub fn execute<F>(&'scope self, job: F) -> Result<(), PoolError>
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+        let job = ScopedJob {
+            job: Some(Box::new(job)),
+            state: Arc::clone(&self.state),
+        };
+        let job = in_submission_span(Box::new(move || job.run()));
+        // SAFETY: `ThreadPool::scope` does not return before the pending
+        // count drops back to zero. `ScopedJob` only decrements it after
+        // the job has been run or dropped, so nothing borrowed for `'scope`
+        // is used after the borrow ends.
+        let job: Job = unsafe { mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+        self.pool.execute(job)
+    }
+}
+
+/// A job executed through a `Scope`, notifying the scope once
+/// it has been run or dropped
+struct ScopedJob<'scope> {
+    job: Option<Box<dyn FnOnce() + Send + 'scope>>,
+    state: Arc<ScopeState>,
+}
+
+impl ScopedJob<'_> {
+    fn run(mut self) {
+        if let Some(job) = self.job.take() {
+            job();
+        }
+    }
+}
+
+impl Drop for ScopedJob<'_> {
     fn drop(&mut self) {
-        drop(self.job_sender.take().unwrap());
-        for worker in &mut self.workers {
-            if let Some(thread) = worker.thread.take() {
-                thread.join().expect(
-                    "Warning: Some workers seem to have panicked. \
-                                      This likely has led to wrong behavior",
-                );
-            }
+        // The job must be dropped before notifying the scope, as its
+        // captured borrows are only guaranteed to live until then
+        drop(self.job.take());
+        if thread::panicking() {
+            self.state.a_job_panicked.store(true, Ordering::Relaxed);
+        }
+
+        let mut pending = self.state.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            self.state.all_done.notify_all();
         }
     }
 }
@@ -0,0 +1,55 @@
+//! Model tests exploring the interleavings of the workers and the threads
+//! using the pool, built on the `loom` primitives
+//!
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --release --test loom`,
+//! bounding the explored interleavings with `LOOM_MAX_PREEMPTIONS=3`
+
+#![cfg(loom)]
+
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use threadpool::ThreadPool;
+
+#[test]
+fn submitted_job_runs_before_drop_returns() {
+    loom::model(|| {
+        let pool = ThreadPool::build(1).unwrap();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_clone = Arc::clone(&runs);
+        pool.execute(move || {
+            runs_clone.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+        drop(pool);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn join_waits_for_jobs_of_concurrent_producers() {
+    loom::model(|| {
+        let pool = ThreadPool::build(1).unwrap();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let producer = {
+            let pool = pool.clone();
+            let runs = Arc::clone(&runs);
+            loom::thread::spawn(move || {
+                pool.execute(move || {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap();
+            })
+        };
+        let runs_clone = Arc::clone(&runs);
+        pool.execute(move || {
+            runs_clone.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+        producer.join().unwrap();
+
+        pool.join();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.completed_count(), 2);
+    });
+}